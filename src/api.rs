@@ -1,26 +1,35 @@
 use anyhow::Result;
-use reqwest::header::AUTHORIZATION;
-use serde_json::Value;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, AUTHORIZATION, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::sync::Mutex;
 use std::time::Duration;
 
 use crate::config::Config;
-use crate::data::{DashboardData, Profile, Recovery, Sleep, Workout};
-use crate::auth::AuthManager;
-
-fn url_encode(s: &str) -> String {
-    s.chars()
-        .map(|c| match c {
-            ':' => "%3A".to_string(),
-            '+' => "%2B".to_string(),
-            ' ' => "%20".to_string(),
-            c => c.to_string(),
-        })
-        .collect()
-}
+use crate::data::{BodyMeasurement, Cycle, DashboardData, HistoryWindow, Profile, Recovery, Sleep, Workout};
+use crate::format::is_too_new;
+use crate::auth::{AuthError, AuthManager};
+use crate::source::{fetch_dashboard, read_window, CacheSource, DataSource};
+use crate::store::Store;
+use crate::sync::Record;
 
-const API_BASE: &str = "https://api.prod.whoop.com/developer";
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
+/// Largest page size the collection endpoints accept.
+const PAGE_LIMIT: u32 = 25;
+/// Upper bound on pages walked for a single range (25 * 400 = 10k records).
+const MAX_PAGES: usize = 400;
+
+/// Retries after the first attempt for transient failures.
+const MAX_RETRIES: u32 = 3;
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+/// Don't sleep through a rate limit longer than this; report it instead.
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(120);
+
 #[derive(Debug, thiserror::Error)]
 pub enum ApiError {
     #[error("API request failed: {endpoint} returned {status} - {message}")]
@@ -29,22 +38,159 @@ pub enum ApiError {
         status: u16,
         message: String,
     },
+    #[error("Rate limited by {endpoint}{}", format_retry_after(.retry_after))]
+    RateLimited {
+        endpoint: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("Unauthorized request to {endpoint}: the access token was rejected")]
+    Unauthorized {
+        endpoint: String,
+    },
+    #[error("Server error: {endpoint} returned {status} - {message}")]
+    ServerError {
+        endpoint: String,
+        status: u16,
+        message: String,
+    },
+    #[error("Network error calling {endpoint}: {source}")]
+    Network {
+        endpoint: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("Request to {endpoint} timed out")]
+    Timeout {
+        endpoint: String,
+    },
+    #[error("{source}")]
+    Auth {
+        #[source]
+        source: anyhow::Error,
+    },
     #[error("Failed to parse response from {endpoint}: {source}")]
     ParseError {
         endpoint: String,
         #[source]
         source: anyhow::Error,
     },
+    #[error("{endpoint} returned more than {pages} pages; narrow the date range")]
+    TooManyPages {
+        endpoint: String,
+        pages: usize,
+    },
+}
+
+impl ApiError {
+    fn from_reqwest(endpoint: &str, source: reqwest::Error) -> Self {
+        if source.is_timeout() {
+            ApiError::Timeout { endpoint: endpoint.to_string() }
+        } else {
+            ApiError::Network { endpoint: endpoint.to_string(), source }
+        }
+    }
+
+    /// Whether retrying the same request may succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ApiError::RateLimited { .. }
+                | ApiError::ServerError { .. }
+                | ApiError::Network { .. }
+                | ApiError::Timeout { .. }
+        )
+    }
+}
+
+fn format_retry_after(retry_after: &Option<Duration>) -> String {
+    match retry_after {
+        Some(wait) => format!("; retry in {}s", wait.as_secs()),
+        None => String::new(),
+    }
+}
+
+/// Request budget reported by the API's `X-RateLimit-*` response headers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub limit: Option<u32>,
+    pub remaining: Option<u32>,
+    /// Time until the budget resets
+    pub reset: Option<Duration>,
+}
+
+impl RateLimit {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let rate_limit = RateLimit {
+            limit: header_number(headers, "x-ratelimit-limit"),
+            remaining: header_number(headers, "x-ratelimit-remaining"),
+            reset: header_number(headers, "x-ratelimit-reset").map(|s| Duration::from_secs(s as u64)),
+        };
+        if rate_limit.limit.is_none() && rate_limit.remaining.is_none() && rate_limit.reset.is_none() {
+            None
+        } else {
+            Some(rate_limit)
+        }
+    }
+}
+
+/// Leading integer of a header value; rate-limit headers may carry extra policy
+/// details such as `100, 100;window=60`.
+fn header_number(headers: &HeaderMap, name: &str) -> Option<u32> {
+    let value = headers.get(name)?.to_str().ok()?.trim();
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Exponential backoff with equal jitter: at least half the exponential delay
+/// plus a random share of the other half.
+fn backoff_delay(attempt: u32) -> Duration {
+    let exponential = BACKOFF_BASE.saturating_mul(1 << attempt.min(16)).min(BACKOFF_MAX);
+    let half = exponential / 2;
+    half + half.mul_f64(rand::random::<f64>())
+}
+
+/// One page of a collection endpoint such as `/v2/recovery`.
+#[derive(Debug, Deserialize)]
+pub struct Page<T> {
+    #[serde(default = "Vec::new")]
+    pub records: Vec<T>,
+    #[serde(default)]
+    pub next_token: Option<String>,
 }
 
+impl<T> Page<T> {
+    /// Token for the page after this one, which was fetched with `current`.
+    /// A missing, empty or repeated token means this is the last page.
+    pub fn next_page(&self, current: Option<&str>) -> Option<String> {
+        self.next_token
+            .clone()
+            .filter(|token| !token.is_empty() && current != Some(token.as_str()))
+    }
+}
+
+/// Progress of a paginated fetch, reported after every page.
+pub struct FetchProgress<'a> {
+    pub endpoint: &'a str,
+    pub page: usize,
+    pub records: usize,
+}
+
+pub type ProgressFn = Box<dyn Fn(&FetchProgress) + Send + Sync>;
+
 pub struct WhoopAPI {
     client: reqwest::Client,
     config: Config,
     auth: AuthManager,
+    progress: Option<ProgressFn>,
+    rate_limit: Mutex<Option<RateLimit>>,
 }
 
 impl WhoopAPI {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self> {
+        Ok(Self::with_config(Config::load(&Config::default())?))
+    }
+
+    pub fn with_config(config: Config) -> Self {
         let client = reqwest::Client::builder()
             .timeout(REQUEST_TIMEOUT)
             .build()
@@ -52,76 +198,170 @@ impl WhoopAPI {
 
         Self {
             client,
-            config: Config::load(),
-            auth: AuthManager::new(),
+            auth: AuthManager::with_config(config.clone()),
+            config,
+            progress: None,
+            rate_limit: Mutex::new(None),
         }
     }
 
+    /// Request budget from the most recent API response, if the API reported one.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// Install (or clear) a callback invoked after each page of a collection fetch.
+    pub fn set_progress(&mut self, progress: Option<ProgressFn>) {
+        self.progress = progress;
+    }
+
     pub async fn authenticate(&self) -> Result<()> {
         self.auth.authenticate().await
     }
 
-    pub async fn test_connection(&self) -> Result<()> {
-        let token = self.auth.get_access_token().await?;
-        let url = format!("{}/v2/user/profile/basic", API_BASE);
-        
-        let response = self.client
-            .get(&url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await?;
+    pub async fn authenticate_without_browser(&self) -> Result<()> {
+        self.auth.authenticate_without_browser().await
+    }
 
-        let status = response.status();
-        if status.is_success() {
-            Ok(())
-        } else {
-            let body = response.text().await.unwrap_or_default();
-            let message = body.chars().take(200).collect::<String>();
-            Err(ApiError::RequestFailed {
-                endpoint: "/v2/user/profile/basic".to_string(),
-                status: status.as_u16(),
-                message,
-            }.into())
-        }
+    pub async fn test_connection(&self) -> Result<()> {
+        self.get_with_retry("/v2/user/profile/basic", &[]).await?;
+        Ok(())
     }
 
-    pub async fn refresh_all_data(&self) -> Result<DashboardData> {
-        let token = self.auth.get_access_token().await?;
-        
-        let profile = self.get_profile(&token).await?;
-        let recovery = self.get_recovery(&token).await?;
-        let sleep = self.get_sleep(&token).await?;
-        let workouts = self.get_workouts(&token).await?;
-
-        let data = DashboardData {
-            profile: Some(profile),
-            recovery,
-            sleep,
-            workouts,
-            refreshed_at: Some(chrono::Utc::now()),
+    /// Fetch every dashboard section for `window` concurrently. A failing
+    /// section keeps its previously cached data and is reported in
+    /// `DashboardData::errors`; only sections that succeeded are written back
+    /// to the cache.
+    pub async fn refresh_all_data(&self, window: HistoryWindow) -> Result<DashboardData> {
+        // Fail once up front rather than in every section when not logged in
+        self.auth.get_access_token().await?;
+
+        let cached = match self.config.load_cache() {
+            Ok(cached) if cached.window == window => cached,
+            // Refreshing would overwrite what a newer whoopterm wrote
+            Err(e) if is_too_new(&e) => return Err(e),
+            _ => DashboardData::default(),
         };
+        let mut store = Store::open(&self.config)?;
+        let data = fetch_dashboard(self, window, cached, &mut store).await?;
+        if let Some(retention) = self.config.retention {
+            store.prune(retention.cutoff(chrono::Utc::now()))?;
+        }
 
-        self.config.save_cache(&data)?;
+        if data.fetched_any() {
+            self.config.save_cache(&data)?;
+        }
         Ok(data)
     }
 
-    pub async fn load_cached_or_refresh(&self) -> Result<DashboardData> {
+    /// Show local data instead when a fetch couldn't get anything from the
+    /// API, e.g. because the network is down. Errors that local data can't
+    /// fix, like having to log in again, are passed on.
+    async fn or_offline(&self, window: HistoryWindow, fetched: Result<DashboardData>) -> Result<DashboardData> {
+        let reason = match &fetched {
+            Ok(data) if data.fetched_any() => return fetched,
+            Ok(data) => data.errors.values().next().cloned().unwrap_or_default(),
+            Err(e) if e.chain().any(|c| c.is::<AuthError>()) || is_too_new(e) => return fetched,
+            Err(e) => format!("{:#}", e),
+        };
+        match CacheSource::new(self.config.clone()).refresh(window).await {
+            Ok(data) => Ok(DashboardData { offline: Some(reason), ..data }),
+            Err(_) => fetched,
+        }
+    }
+
+    pub async fn load_cached_or_refresh(&self, window: HistoryWindow) -> Result<DashboardData> {
         if let Ok(cached) = self.config.load_cache() {
-            if let Some(refreshed_at) = cached.refreshed_at {
-                let age = chrono::Utc::now().signed_duration_since(refreshed_at);
-                if age.num_seconds() < 3600 { // 1 hour cache
-                    return Ok(cached);
+            // A cache fetched for a different window can't stand in for this one
+            if cached.window == window {
+                // Sections that failed last time are retried instead of waiting out the TTL
+                if let Some(fetched_at) = cached.oldest_fetch() {
+                    let age = chrono::Utc::now().signed_duration_since(fetched_at);
+                    if age < self.config.cache_ttl() {
+                        let mut data = cached;
+                        read_window(&Store::open(&self.config)?, &mut data)?;
+                        return Ok(data);
+                    }
                 }
             }
         }
 
-        self.refresh_all_data().await
+        self.refresh_all_data(window).await
+    }
+
+    /// GET an endpoint, retrying transient failures with exponential backoff.
+    /// Rate-limited requests wait for `Retry-After` (or the rate-limit reset)
+    /// when the API provides one. A 401 forces one token refresh and retry.
+    async fn get_with_retry(&self, endpoint: &str, query: &[(&str, String)]) -> Result<String, ApiError> {
+        let url = format!("{}{}", self.config.api_base(), endpoint);
+        let mut token = self.auth.get_access_token().await
+            .map_err(|source| ApiError::Auth { source })?;
+        let mut reauthenticated = false;
+        let mut attempt = 0;
+
+        loop {
+            let result = match self.client
+                .get(&url)
+                .header(AUTHORIZATION, format!("Bearer {}", token))
+                .query(query)
+                .send()
+                .await
+            {
+                Ok(response) => self.check_response(response, endpoint).await,
+                Err(e) => Err(ApiError::from_reqwest(endpoint, e)),
+            };
+
+            let error = match result {
+                Err(ApiError::Unauthorized { .. }) if !reauthenticated => {
+                    token = self.auth.refresh_access_token(&token).await
+                        .map_err(|source| ApiError::Auth { source })?;
+                    reauthenticated = true;
+                    continue;
+                }
+                Err(ApiError::Unauthorized { endpoint }) => {
+                    return Err(ApiError::Auth {
+                        source: AuthError::ReauthRequired {
+                            reason: format!("{} rejected a freshly refreshed token", endpoint),
+                        }.into(),
+                    });
+                }
+                Err(error) if error.is_transient() && attempt < MAX_RETRIES => error,
+                other => return other,
+            };
+
+            let delay = match &error {
+                ApiError::RateLimited { retry_after: Some(wait), .. } if *wait > MAX_RETRY_WAIT => return Err(error),
+                ApiError::RateLimited { retry_after: Some(wait), .. } => *wait,
+                _ => backoff_delay(attempt),
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
     }
 
     async fn check_response(&self, response: reqwest::Response, endpoint: &str) -> Result<String, ApiError> {
         let status = response.status();
         let endpoint_str = endpoint.to_string();
-        
+
+        let rate_limit = RateLimit::from_headers(response.headers());
+        if rate_limit.is_some() {
+            *self.rate_limit.lock().unwrap() = rate_limit;
+        }
+
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = header_number(response.headers(), RETRY_AFTER.as_str())
+                .map(|s| Duration::from_secs(s as u64))
+                .or(rate_limit.and_then(|r| r.reset));
+            return Err(ApiError::RateLimited {
+                endpoint: endpoint_str,
+                retry_after,
+            });
+        }
+
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(ApiError::Unauthorized { endpoint: endpoint_str });
+        }
+
         if !status.is_success() {
             let body = response.text().await.unwrap_or_default();
             let message = if body.is_empty() {
@@ -129,6 +369,13 @@ impl WhoopAPI {
             } else {
                 body.chars().take(500).collect::<String>()
             };
+            if status.is_server_error() {
+                return Err(ApiError::ServerError {
+                    endpoint: endpoint_str,
+                    status: status.as_u16(),
+                    message,
+                });
+            }
             return Err(ApiError::RequestFailed {
                 endpoint: endpoint_str,
                 status: status.as_u16(),
@@ -136,162 +383,168 @@ impl WhoopAPI {
             });
         }
         
-        response.text().await.map_err(|e| ApiError::RequestFailed {
-            endpoint: endpoint_str.clone(),
-            status: status.as_u16(),
-            message: format!("Failed to read response body: {}", e),
-        })
+        response.text().await.map_err(|e| ApiError::from_reqwest(endpoint, e))
     }
 
-    async fn get_profile(&self, token: &str) -> Result<Profile> {
-        let url = format!("{}/v2/user/profile/basic", API_BASE);
-        let endpoint = "/v2/user/profile/basic";
-        
-        let response = self.client
-            .get(&url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await?;
-
-        let body = self.check_response(response, endpoint).await?;
-        let profile = serde_json::from_str::<Profile>(&body)
+    async fn get_profile(&self) -> Result<Profile> {
+        self.fetch_object("/v2/user/profile/basic").await
+    }
+
+    async fn get_body_measurement(&self) -> Result<BodyMeasurement> {
+        self.fetch_object("/v2/user/measurement/body").await
+    }
+
+    async fn get_recovery(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Recovery>> {
+        self.fetch_collection(Recovery::ENDPOINT, start, end).await
+    }
+
+    async fn get_sleep(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Sleep>> {
+        self.fetch_collection(Sleep::ENDPOINT, start, end).await
+    }
+
+    async fn get_workouts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Workout>> {
+        self.fetch_collection(Workout::ENDPOINT, start, end).await
+    }
+
+    async fn get_cycles(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Cycle>> {
+        self.fetch_collection(Cycle::ENDPOINT, start, end).await
+    }
+
+    /// Fetch a single, non-paginated resource such as the user profile.
+    async fn fetch_object<T: DeserializeOwned>(&self, endpoint: &str) -> Result<T> {
+        let body = self.get_with_retry(endpoint, &[]).await?;
+        let object = serde_json::from_str::<T>(&body)
             .map_err(|e| ApiError::ParseError {
                 endpoint: endpoint.to_string(),
                 source: anyhow::anyhow!("{} (body excerpt: {})", e, &body[..body.len().min(200)]),
             })?;
-        
-        Ok(profile)
+
+        Ok(object)
     }
 
-    async fn get_recovery(&self, token: &str) -> Result<Vec<Recovery>> {
-        let end = chrono::Utc::now();
-        let start = end - chrono::Duration::days(7);
-        
-        let url = format!(
-            "{}/v2/recovery?start={}&end={}",
-            API_BASE,
-            url_encode(&start.to_rfc3339()),
-            url_encode(&end.to_rfc3339())
-        );
-        let endpoint = "/v2/recovery";
-        
-        let response = self.client
-            .get(&url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await?;
-
-        let body = self.check_response(response, endpoint).await?;
-        let json: Value = serde_json::from_str(&body)
-            .map_err(|e| ApiError::ParseError {
-                endpoint: endpoint.to_string(),
-                source: anyhow::anyhow!("Failed to parse JSON: {} (body excerpt: {})", e, &body[..body.len().min(200)]),
-            })?;
-        
-        let records = json["records"]
-            .as_array()
-            .cloned()
-            .unwrap_or_default();
-        
-        let recovery: Vec<Recovery> = serde_json::from_value(serde_json::Value::Array(records))
+    /// Fetch one page of a collection endpoint between `start` and `end`.
+    pub async fn fetch_page<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        next_token: Option<&str>,
+    ) -> Result<Page<T>> {
+        let mut query = vec![
+            ("limit", PAGE_LIMIT.to_string()),
+            ("start", start.to_rfc3339()),
+            ("end", end.to_rfc3339()),
+        ];
+        if let Some(next) = next_token {
+            query.push(("nextToken", next.to_string()));
+        }
+
+        let body = self.get_with_retry(endpoint, &query).await?;
+        let page = serde_json::from_str(&body)
             .map_err(|e| ApiError::ParseError {
                 endpoint: endpoint.to_string(),
-                source: anyhow::anyhow!("Failed to parse recovery records: {}", e),
+                source: anyhow::anyhow!("Failed to parse page: {} (body excerpt: {})", e, &body[..body.len().min(200)]),
             })?;
-        
-        // Filter out unscored records for cleaner display
-        let scored: Vec<Recovery> = recovery.into_iter()
-            .filter(|r| r.score.is_some())
-            .collect();
-        
-        Ok(scored)
+        Ok(page)
     }
 
-    async fn get_sleep(&self, token: &str) -> Result<Vec<Sleep>> {
-        let end = chrono::Utc::now();
-        let start = end - chrono::Duration::days(7);
-        
-        let url = format!(
-            "{}/v2/activity/sleep?start={}&end={}",
-            API_BASE,
-            url_encode(&start.to_rfc3339()),
-            url_encode(&end.to_rfc3339())
-        );
-        let endpoint = "/v2/activity/sleep";
-        
-        let response = self.client
-            .get(&url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await?;
-
-        let body = self.check_response(response, endpoint).await?;
-        let json: Value = serde_json::from_str(&body)
-            .map_err(|e| ApiError::ParseError {
-                endpoint: endpoint.to_string(),
-                source: anyhow::anyhow!("Failed to parse JSON: {} (body excerpt: {})", e, &body[..body.len().min(200)]),
-            })?;
-        
-        let records = json["records"]
-            .as_array()
-            .cloned()
-            .unwrap_or_default();
-        
-        let sleep: Vec<Sleep> = serde_json::from_value(serde_json::Value::Array(records))
-            .map_err(|e| ApiError::ParseError {
-                endpoint: endpoint.to_string(),
-                source: anyhow::anyhow!("Failed to parse sleep records: {}", e),
-            })?;
-        
-        // Filter out unscored and nap records for main display
-        let scored_nights: Vec<Sleep> = sleep.into_iter()
-            .filter(|s| s.score.is_some() && !s.nap)
-            .collect();
-        
-        Ok(scored_nights)
+    /// Wait out the rate-limit window when the last response said no requests
+    /// are left, so long runs slow down instead of collecting 429s.
+    pub async fn wait_for_rate_limit(&self) {
+        let Some(rate_limit) = self.rate_limit() else {
+            return;
+        };
+        if rate_limit.remaining == Some(0) {
+            if let Some(reset) = rate_limit.reset {
+                tokio::time::sleep(reset.min(MAX_RETRY_WAIT)).await;
+            }
+        }
     }
 
-    async fn get_workouts(&self, token: &str) -> Result<Vec<Workout>> {
-        let end = chrono::Utc::now();
-        let start = end - chrono::Duration::days(7);
-        
-        let url = format!(
-            "{}/v2/activity/workout?start={}&end={}",
-            API_BASE,
-            url_encode(&start.to_rfc3339()),
-            url_encode(&end.to_rfc3339())
-        );
-        let endpoint = "/v2/activity/workout";
-        
-        let response = self.client
-            .get(&url)
-            .header(AUTHORIZATION, format!("Bearer {}", token))
-            .send()
-            .await?;
-
-        let body = self.check_response(response, endpoint).await?;
-        let json: Value = serde_json::from_str(&body)
-            .map_err(|e| ApiError::ParseError {
-                endpoint: endpoint.to_string(),
-                source: anyhow::anyhow!("Failed to parse JSON: {} (body excerpt: {})", e, &body[..body.len().min(200)]),
-            })?;
-        
-        let records = json["records"]
-            .as_array()
-            .cloned()
-            .unwrap_or_default();
-        
-        let workouts: Vec<Workout> = serde_json::from_value(serde_json::Value::Array(records))
-            .map_err(|e| ApiError::ParseError {
-                endpoint: endpoint.to_string(),
-                source: anyhow::anyhow!("Failed to parse workout records: {}", e),
-            })?;
-        
-        // Filter out unscored records
-        let scored: Vec<Workout> = workouts.into_iter()
-            .filter(|w| w.score.is_some())
-            .collect();
-        
-        Ok(scored)
+    /// Fetch every record of a paginated collection endpoint between `start`
+    /// and `end`, following `next_token` until the API reports no more pages.
+    async fn fetch_collection<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<T>> {
+        let mut records: Vec<T> = Vec::new();
+        let mut next_token: Option<String> = None;
+        let mut pages = 0;
+
+        loop {
+            if pages >= MAX_PAGES {
+                return Err(ApiError::TooManyPages {
+                    endpoint: endpoint.to_string(),
+                    pages,
+                }.into());
+            }
+
+            let page: Page<T> = self.fetch_page(endpoint, start, end, next_token.as_deref()).await?;
+            pages += 1;
+            let next_page = page.next_page(next_token.as_deref());
+            records.extend(page.records);
+
+            if let Some(progress) = &self.progress {
+                progress(&FetchProgress {
+                    endpoint,
+                    page: pages,
+                    records: records.len(),
+                });
+            }
+
+            match next_page {
+                Some(token) => next_token = Some(token),
+                None => break,
+            }
+        }
+
+        Ok(records)
+    }
+}
+
+#[async_trait]
+impl DataSource for WhoopAPI {
+    fn name(&self) -> &'static str {
+        "live"
+    }
+
+    async fn profile(&self) -> Result<Profile> {
+        self.get_profile().await
+    }
+
+    async fn body_measurement(&self) -> Result<BodyMeasurement> {
+        self.get_body_measurement().await
+    }
+
+    async fn recovery(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Recovery>> {
+        self.get_recovery(start, end).await
+    }
+
+    async fn sleep(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Sleep>> {
+        self.get_sleep(start, end).await
+    }
+
+    async fn workouts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Workout>> {
+        self.get_workouts(start, end).await
+    }
+
+    async fn cycles(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Cycle>> {
+        self.get_cycles(start, end).await
+    }
+
+    async fn load(&self, window: HistoryWindow) -> Result<DashboardData> {
+        let fetched = self.load_cached_or_refresh(window).await;
+        self.or_offline(window, fetched).await
+    }
+
+    async fn refresh(&self, window: HistoryWindow) -> Result<DashboardData> {
+        let fetched = self.refresh_all_data(window).await;
+        self.or_offline(window, fetched).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        WhoopAPI::rate_limit(self)
     }
 }
@@ -1,18 +1,71 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use oauth2::{AuthType, AuthorizationCode, AuthUrl, ClientId, ClientSecret, CsrfToken, RedirectUrl, Scope, TokenResponse, TokenUrl};
+use oauth2::{
+    AuthType, AuthorizationCode, AuthUrl, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
+};
 use oauth2::basic::BasicClient;
-use std::io::{BufRead, BufReader, Write};
-use std::net::TcpListener;
+use oauth2::url::Url;
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
 
 use crate::config::Config;
+use crate::format::is_too_new;
 
-const AUTH_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/auth";
-const TOKEN_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/token";
-const REDIRECT_URI: &str = "http://localhost:8080/callback";
+/// Refresh tokens this long before they expire locally.
+fn expires_soon(tokens: &Tokens) -> bool {
+    chrono::Utc::now() >= tokens.expires_at - chrono::Duration::minutes(5)
+}
 
 pub struct AuthManager {
     config: Config,
+    /// Serializes refresh-token exchanges: WHOOP rotates refresh tokens, so two
+    /// concurrent exchanges with the same one would invalidate each other.
+    refresh_lock: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum AuthError {
+    #[error("Not authenticated. Run: whoopterm --auth")]
+    NotAuthenticated,
+    #[error("Re-authentication required ({reason}). Run: whoopterm --auth")]
+    ReauthRequired { reason: String },
+}
+
+/// The `ReauthRequired` cause of `error`, meaning the stored tokens are
+/// unusable and the user must log in again.
+pub fn reauth_cause(error: &anyhow::Error) -> Option<&AuthError> {
+    error.chain()
+        .filter_map(|cause| cause.downcast_ref::<AuthError>())
+        .find(|cause| matches!(cause, AuthError::ReauthRequired { .. }))
+}
+
+/// Whether the token endpoint refused the stored refresh token or client.
+/// Anything else, e.g. no network, a 5xx or an HTML error page, may pass.
+fn rejects_tokens(error: &anyhow::Error) -> bool {
+    use oauth2::basic::BasicErrorResponseType::{InvalidClient, InvalidGrant, UnauthorizedClient};
+    type ExchangeError = oauth2::RequestTokenError<oauth2::reqwest::Error<reqwest::Error>, oauth2::basic::BasicErrorResponse>;
+    error.chain().any(|cause| match cause.downcast_ref::<ExchangeError>() {
+        Some(oauth2::RequestTokenError::ServerResponse(response)) => {
+            matches!(response.error(), InvalidGrant | InvalidClient | UnauthorizedClient)
+        }
+        _ => false,
+    })
+}
+
+/// A login in progress: the URL to send the user to, and what's needed to
+/// check and redeem the code WHOOP redirects back with.
+pub struct Login {
+    pub url: Url,
+    state: CsrfToken,
+    verifier: PkceCodeVerifier,
+}
+
+impl Login {
+    pub fn state(&self) -> &str {
+        self.state.secret()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,54 +76,53 @@ pub struct Tokens {
 }
 
 impl AuthManager {
-    pub fn new() -> Self {
+    pub fn with_config(config: Config) -> Self {
         Self {
-            config: Config::load(),
+            config,
+            refresh_lock: tokio::sync::Mutex::new(()),
         }
     }
 
-    pub async fn authenticate(&self) -> Result<()> {
+    /// OAuth client for the configured WHOOP app. Without a client secret
+    /// it acts as a public client and relies on PKCE alone.
+    fn client(&self) -> Result<BasicClient> {
         let client_id = self.config.client_id.as_ref()
             .context("Client ID not configured")?;
-        let client_secret = self.config.client_secret.as_ref()
-            .context("Client secret not configured")?;
 
-        let client = BasicClient::new(
+        Ok(BasicClient::new(
             ClientId::new(client_id.clone()),
-            Some(ClientSecret::new(client_secret.clone())),
-            AuthUrl::new(AUTH_URL.to_string())?,
-            Some(TokenUrl::new(TOKEN_URL.to_string())?),
+            self.config.client_secret.clone().map(ClientSecret::new),
+            AuthUrl::new(self.config.auth_url().to_string())?,
+            Some(TokenUrl::new(self.config.token_url().to_string())?),
         )
-        .set_redirect_uri(RedirectUrl::new(REDIRECT_URI.to_string())?)
-        .set_auth_type(AuthType::RequestBody);
+        .set_redirect_uri(RedirectUrl::new(self.config.redirect_uri().to_string())?)
+        .set_auth_type(AuthType::RequestBody))
+    }
 
-        let (auth_url, _csrf_token) = client
+    /// Build the authorization URL for a new login, with a fresh `state` and
+    /// PKCE challenge.
+    pub fn start_login(&self) -> Result<Login> {
+        let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
+        let (url, state) = self.client()?
             .authorize_url(CsrfToken::new_random)
             .add_scope(Scope::new("read:recovery".to_string()))
             .add_scope(Scope::new("read:sleep".to_string()))
             .add_scope(Scope::new("read:workout".to_string()))
             .add_scope(Scope::new("read:cycles".to_string()))
             .add_scope(Scope::new("read:profile".to_string()))
+            .add_scope(Scope::new("read:body_measurement".to_string()))
             .add_scope(Scope::new("offline".to_string()))
+            .set_pkce_challenge(challenge)
             .url();
+        Ok(Login { url, state, verifier })
+    }
 
-        // Open browser
-        #[cfg(target_os = "macos")]
-        std::process::Command::new("open").arg(auth_url.as_str()).spawn()?;
-        
-        #[cfg(target_os = "linux")]
-        std::process::Command::new("xdg-open").arg(auth_url.as_str()).spawn()?;
-        
-        println!("Opening browser for WHOOP authentication...");
-        println!("If the browser doesn't open, visit: {}", auth_url);
-
-        // Start local server to receive callback
-        let code = self.receive_auth_code().await?;
-        eprintln!("Received auth code: {}...", &code[..code.len().min(10)]);
-
-        // Exchange code for token
-        let token = client
+    /// Exchange the authorization code `login` was answered with for tokens,
+    /// and store them.
+    pub async fn finish_login(&self, login: Login, code: String) -> Result<()> {
+        let token = self.client()?
             .exchange_code(AuthorizationCode::new(code))
+            .set_pkce_verifier(login.verifier)
             .request_async(oauth2::reqwest::async_http_client)
             .await
             .map_err(|e| anyhow::anyhow!("Token exchange failed: {:?}", e))?;
@@ -80,44 +132,103 @@ impl AuthManager {
             refresh_token: token.refresh_token().map(|t| t.secret().clone()),
             expires_at: chrono::Utc::now() + chrono::Duration::seconds(token.expires_in().unwrap_or_default().as_secs() as i64),
         };
+        self.config.save_tokens(&tokens)
+    }
+
+    pub async fn authenticate(&self) -> Result<()> {
+        let login = self.start_login()?;
+        // Listen before opening the browser, so a busy port fails right away
+        let listener = self.bind_callback().await?;
+
+        println!("Opening browser for WHOOP authentication...");
+        if let Err(e) = open_browser(&login.url) {
+            eprintln!("Couldn't open a browser ({}); try whoopterm --auth --no-browser", e);
+        }
+        println!("If the browser doesn't open, visit: {}", login.url);
 
-        self.config.save_tokens(&tokens)?;
+        let code = self.receive_auth_code(listener, login.state()).await?;
+        eprintln!("Received auth code: {}...", &code[..code.len().min(10)]);
+
+        self.finish_login(login, code).await?;
         println!("Authentication successful!");
 
         Ok(())
     }
 
-    pub async fn get_access_token(&self) -> Result<String> {
-        if let Ok(tokens) = self.config.load_tokens() {
-            if chrono::Utc::now() < tokens.expires_at - chrono::Duration::minutes(5) {
-                return Ok(tokens.access_token);
-            }
+    /// Log in without a browser or callback listener on this machine: the user
+    /// opens the URL anywhere and pastes back where WHOOP redirected them.
+    pub async fn authenticate_without_browser(&self) -> Result<()> {
+        use tokio::io::AsyncBufReadExt;
 
-            // Token expired, try to refresh
-            if let Some(refresh_token) = &tokens.refresh_token {
-                if let Ok(new_tokens) = self.refresh_token(refresh_token).await {
-                    self.config.save_tokens(&new_tokens)?;
-                    return Ok(new_tokens.access_token);
-                }
+        let login = self.start_login()?;
+        println!("Open this URL in a browser and log in to WHOOP:\n\n{}\n", login.url);
+        println!("WHOOP then redirects to {} (the page may fail to load; that's fine).", self.config.redirect_uri());
+        println!("Paste the full address from the browser's address bar, or just its code:");
+
+        let mut stdin = tokio::io::BufReader::new(tokio::io::stdin());
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if stdin.read_line(&mut line).await? == 0 {
+                anyhow::bail!("No authorization code was entered");
+            }
+            if !line.trim().is_empty() {
+                break;
             }
         }
+        let code = parse_pasted(&line, login.state())?;
 
-        Err(anyhow::anyhow!("Not authenticated. Run: whoopterm --auth"))
+        self.finish_login(login, code).await?;
+        println!("Authentication successful!");
+
+        Ok(())
     }
 
-    async fn refresh_token(&self, refresh_token: &str) -> Result<Tokens> {
-        let client_id = self.config.client_id.as_ref()
-            .context("Client ID not configured")?;
-        let client_secret = self.config.client_secret.as_ref()
-            .context("Client secret not configured")?;
+    /// Stored tokens; unreadable ones mean logging in again, unless they're
+    /// from a newer whoopterm and logging in would overwrite them.
+    fn load_tokens(&self) -> Result<Tokens> {
+        self.config.load_tokens().map_err(|e| if is_too_new(&e) { e } else { AuthError::NotAuthenticated.into() })
+    }
 
-        let client = BasicClient::new(
-            ClientId::new(client_id.clone()),
-            Some(ClientSecret::new(client_secret.clone())),
-            AuthUrl::new(AUTH_URL.to_string())?,
-            Some(TokenUrl::new(TOKEN_URL.to_string())?),
-        )
-        .set_auth_type(AuthType::RequestBody);
+    pub async fn get_access_token(&self) -> Result<String> {
+        let tokens = self.load_tokens()?;
+        if !expires_soon(&tokens) {
+            return Ok(tokens.access_token);
+        }
+
+        // Token expired, try to refresh
+        self.refresh_access_token(&tokens.access_token).await
+    }
+
+    /// Exchange the refresh token for a new access token, e.g. because the API
+    /// rejected `stale_token` even though it hadn't expired locally. The rotated
+    /// tokens are persisted before returning.
+    pub async fn refresh_access_token(&self, stale_token: &str) -> Result<String> {
+        let _guard = self.refresh_lock.lock().await;
+
+        let tokens = self.load_tokens()?;
+        // Another request already refreshed while we waited for the lock
+        if tokens.access_token != stale_token && !expires_soon(&tokens) {
+            return Ok(tokens.access_token);
+        }
+
+        let refresh_token = tokens.refresh_token.clone().ok_or_else(|| AuthError::ReauthRequired {
+            reason: "no refresh token stored".to_string(),
+        })?;
+        let mut new_tokens = self.refresh_token(&refresh_token).await.map_err(|e| {
+            if rejects_tokens(&e) { AuthError::ReauthRequired { reason: format!("{:#}", e) }.into() } else { e }
+        })?;
+        // Keep the previous refresh token if the server didn't rotate it
+        if new_tokens.refresh_token.is_none() {
+            new_tokens.refresh_token = Some(refresh_token);
+        }
+
+        self.config.save_tokens(&new_tokens)?;
+        Ok(new_tokens.access_token)
+    }
+
+    async fn refresh_token(&self, refresh_token: &str) -> Result<Tokens> {
+        let client = self.client()?;
 
         let token = client
             .exchange_refresh_token(&oauth2::RefreshToken::new(refresh_token.to_string()))
@@ -132,38 +243,226 @@ impl AuthManager {
         })
     }
 
-    async fn receive_auth_code(&self) -> Result<String> {
-        let listener = TcpListener::bind("127.0.0.1:8080")
-            .context("Failed to bind to port 8080")?;
-        
-        println!("Waiting for authentication...");
-
-        for stream in listener.incoming() {
-            let stream = stream?;
-            let mut reader = BufReader::new(&stream);
-            let mut line = String::new();
-            
-            reader.read_line(&mut line)?;
-            
-            // Parse request line
-            if line.starts_with("GET /callback") {
-                // Extract code from URL
-                if let Some(code_start) = line.find("code=") {
-                    let code = line[code_start + 5..]
-                        .split(&['&', ' '][..])
-                        .next()
-                        .context("Failed to parse auth code")?;
-                    
-                    // Send response
-                    let response = "HTTP/1.1 200 OK\r\nContent-Length: 32\r\n\r\nAuthentication successful!";
-                    let mut writer = &stream;
-                    writer.write_all(response.as_bytes())?;
-                    
-                    return Ok(code.to_string());
+    async fn bind_callback(&self) -> Result<TcpListener> {
+        let redirect = Url::parse(self.config.redirect_uri())?;
+        let (address, port) = self.config.callback_bind()?;
+        let explicit_port = self.config.callback_port.is_some();
+        TcpListener::bind((address.as_str(), port)).await.map_err(|e| bind_error(e, &address, port, &redirect, explicit_port))
+    }
+
+    async fn receive_auth_code(&self, listener: TcpListener, state: &str) -> Result<String> {
+        let redirect = Url::parse(self.config.redirect_uri())?;
+        let timeout = self.config.login_timeout();
+
+        println!("Waiting for authentication (up to {} seconds, Ctrl-C to cancel)...", timeout.as_secs());
+        receive_callback(listener, &redirect, state, timeout).await
+    }
+}
+
+/// Wait for WHOOP to redirect the browser back, until `timeout` runs out or
+/// the user presses Ctrl-C.
+pub async fn receive_callback(listener: TcpListener, redirect: &Url, expected_state: &str, timeout: std::time::Duration) -> Result<String> {
+    tokio::select! {
+        code = serve_callback(listener, redirect, expected_state) => code,
+        _ = tokio::time::sleep(timeout) => Err(anyhow::anyhow!(
+            "No login arrived within {} seconds; run whoopterm --auth again (or raise --login-timeout)",
+            timeout.as_secs(),
+        )),
+        _ = tokio::signal::ctrl_c() => Err(anyhow::anyhow!("Login cancelled")),
+    }
+}
+
+/// How long a single browser connection gets to send its request.
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Answer requests on `listener` until one arrives at the `redirect` path
+/// carrying a login response, and return its authorization code. Other
+/// requests, like the browser asking for a favicon, get a 404 and the wait
+/// goes on. Connections are served concurrently, so one the browser opened
+/// speculatively and left idle can't hold up the redirect.
+pub async fn serve_callback(listener: TcpListener, redirect: &Url, expected_state: &str) -> Result<String> {
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                connections.spawn(serve_connection(stream, redirect.clone(), expected_state.to_string()));
+            }
+            Some(served) = connections.join_next() => {
+                if let Ok(Some(result)) = served {
+                    return Ok(result?);
                 }
             }
         }
+    }
+}
+
+/// Answer one connection, returning the login response if it carried one.
+async fn serve_connection(mut stream: TcpStream, redirect: Url, expected_state: String) -> Option<Result<String, CallbackError>> {
+    let target = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some(target))) => target,
+        Ok(Ok(None)) => {
+            respond(&mut stream, "405 Method Not Allowed", &page("Not supported", "whoopterm only accepts the login redirect here.")).await;
+            return None;
+        }
+        // A connection that closed or stalled before sending a request
+        Ok(Err(_)) | Err(_) => return None,
+    };
+
+    let url = match redirect.join(&target) {
+        Ok(url) if url.path() == redirect.path() && url.query_pairs().any(|(key, _)| key == "code" || key == "error") => url,
+        _ => {
+            respond(&mut stream, "404 Not Found", &page("Not found", "This isn't the whoopterm login page.")).await;
+            return None;
+        }
+    };
+
+    let result = parse_callback(&url, &expected_state);
+    let (status, body) = match &result {
+        Ok(_) => ("200 OK", page("Logged in", "whoopterm is now connected to WHOOP. You can close this window.")),
+        Err(e) => ("400 Bad Request", page("Login failed", &e.to_string())),
+    };
+    respond(&mut stream, status, &body).await;
+    Some(result)
+}
+
+/// Read a request's head and return its target, or `None` for methods
+/// other than GET.
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Option<String>> {
+    // Request heads from browsers are well under this
+    const MAX_HEAD: usize = 16 * 1024;
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_HEAD {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    // Request line: GET <target> HTTP/1.1
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => Ok(Some(target.to_string())),
+        (Some(_), Some(_)) => Ok(None),
+        _ => Err(std::io::ErrorKind::InvalidData.into()),
+    }
+}
+
+/// Send an HTML response and close the connection. The browser going away
+/// first isn't worth failing the login over, so write errors are ignored.
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body,
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+fn page(title: &str, message: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>whoopterm: {title}</title></head>\n\
+         <body style=\"font-family: sans-serif; max-width: 40em; margin: 4em auto\">\n\
+         <h1>{title}</h1>\n<p>{message}</p>\n</body></html>\n",
+        title = escape_html(title),
+        message = escape_html(message),
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn open_browser(url: &Url) -> std::io::Result<()> {
+    #[cfg(target_os = "macos")]
+    std::process::Command::new("open").arg(url.as_str()).spawn()?;
+
+    #[cfg(target_os = "linux")]
+    std::process::Command::new("xdg-open").arg(url.as_str()).spawn()?;
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    let _ = url;
+
+    Ok(())
+}
 
-        Err(anyhow::anyhow!("Failed to receive auth code"))
+/// Explain how to get past a callback port that can't be used. A busy port is
+/// replaced through whichever setting chose it: `callback_port` when it's set
+/// (`explicit_port`), otherwise the redirect URI.
+fn bind_error(error: std::io::Error, address: &str, port: u16, redirect: &Url, explicit_port: bool) -> anyhow::Error {
+    if error.kind() != std::io::ErrorKind::AddrInUse {
+        return anyhow::Error::new(error).context(format!("Failed to listen for the login callback on {}:{}", address, port));
+    }
+    let busy = format!("Port {port} on {address} is already in use, so the login callback can't be received there.");
+    let Some(free_port) = free_port(address, port) else {
+        return anyhow::anyhow!("{busy}\nFree the port or pick another one, and try again.");
+    };
+    if explicit_port {
+        return anyhow::anyhow!(
+            "{busy}\n\
+             Use a free port instead: --callback-port {free_port}\n\
+             (or callback_port in config.json, or WHOOP_CALLBACK_PORT), and make sure {redirect} reaches it."
+        );
+    }
+    let mut suggested = redirect.clone();
+    let _ = suggested.set_port(Some(free_port));
+    anyhow::anyhow!(
+        "{busy}\n\
+         Use a free port instead: --redirect-uri {suggested}\n\
+         (or redirect_uri in config.json, or WHOOP_REDIRECT_URI), and add {suggested} as a\n\
+         redirect URI of your app at https://developer-dashboard.whoop.com so WHOOP accepts it."
+    )
+}
+
+/// A port near 8765 that can be listened on at `address` right now.
+fn free_port(address: &str, busy: u16) -> Option<u16> {
+    (8765..8800)
+        .filter(|port| *port != busy)
+        .find(|port| std::net::TcpListener::bind((address, *port)).is_ok())
+}
+
+/// Why the OAuth redirect didn't deliver a usable authorization code.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum CallbackError {
+    #[error("WHOOP refused the login ({error}){}", .description.as_ref().map(|d| format!(": {}", d)).unwrap_or_default())]
+    Denied { error: String, description: Option<String> },
+    #[error("The login response doesn't belong to this login attempt (state mismatch); start again with whoopterm --auth")]
+    StateMismatch,
+    #[error("The login response has no authorization code")]
+    MissingCode,
+}
+
+/// Extract the authorization code from the URL WHOOP redirected the browser
+/// to, checking that its `state` is the one this login sent.
+pub fn parse_callback(url: &Url, expected_state: &str) -> Result<String, CallbackError> {
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    if let Some(error) = params.get("error") {
+        return Err(CallbackError::Denied {
+            error: error.clone(),
+            description: params.get("error_description").cloned(),
+        });
+    }
+    if params.get("state").map(String::as_str) != Some(expected_state) {
+        return Err(CallbackError::StateMismatch);
+    }
+    params.get("code").filter(|code| !code.is_empty()).cloned().ok_or(CallbackError::MissingCode)
+}
+
+/// Extract the authorization code from what the user pasted for a headless
+/// login: the redirect URL (state-checked like a real callback) or the bare
+/// code copied out of it.
+pub fn parse_pasted(input: &str, expected_state: &str) -> Result<String, CallbackError> {
+    let input = input.trim();
+    match input.split_once('?') {
+        Some((_, query)) => {
+            let mut url = Url::parse("http://localhost/").expect("static URL");
+            url.set_query(Some(query));
+            parse_callback(&url, expected_state)
+        }
+        None if input.is_empty() || input.contains(char::is_whitespace) => Err(CallbackError::MissingCode),
+        None => Ok(input.to_string()),
     }
 }
@@ -0,0 +1,101 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::api::{Page, WhoopAPI};
+use crate::data::{Cycle, Recovery, Section, Sleep, Workout};
+use crate::store::{Store, Stored};
+use crate::sync::Record;
+
+/// Earliest date a backfill asks for; WHOOP has no data from before this.
+fn history_start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2012, 1, 1, 0, 0, 0).unwrap()
+}
+
+/// How far a backfill got, saved after every page so an interrupted run can
+/// pick up where it stopped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub collections: BTreeMap<Section, CollectionProgress>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CollectionProgress {
+    /// Everything starting from here up to when the backfill began is stored;
+    /// pages come newest first, so this moves back towards the first record
+    pub until: DateTime<Utc>,
+    /// Records fetched so far
+    pub fetched: usize,
+    /// Fetched records that were new or newer than the stored copy
+    pub written: usize,
+    pub done: bool,
+}
+
+/// Walk the account's entire history into `store`, one collection at a time,
+/// resuming an earlier backfill that didn't finish. `progress` is called after
+/// every page.
+pub async fn backfill(
+    api: &WhoopAPI,
+    store: &mut Store,
+    progress: impl Fn(Section, &CollectionProgress),
+) -> Result<Checkpoint> {
+    let mut checkpoint = store.backfill_checkpoint()?;
+    let started = Utc::now();
+
+    backfill_collection::<Recovery>(api, store, &mut checkpoint, started, &progress).await?;
+    backfill_collection::<Sleep>(api, store, &mut checkpoint, started, &progress).await?;
+    backfill_collection::<Workout>(api, store, &mut checkpoint, started, &progress).await?;
+    backfill_collection::<Cycle>(api, store, &mut checkpoint, started, &progress).await?;
+
+    // A finished backfill starts over next time rather than resuming
+    store.clear_backfill_checkpoint()?;
+    Ok(checkpoint)
+}
+
+async fn backfill_collection<T: Stored>(
+    api: &WhoopAPI,
+    store: &mut Store,
+    checkpoint: &mut Checkpoint,
+    started: DateTime<Utc>,
+    progress: &impl Fn(Section, &CollectionProgress),
+) -> Result<()> {
+    let mut state = *checkpoint.collections.entry(T::SECTION).or_insert(CollectionProgress {
+        until: started,
+        fetched: 0,
+        written: 0,
+        done: false,
+    });
+    if state.done {
+        return Ok(());
+    }
+
+    // Page tokens don't survive between runs, so resume by narrowing the range
+    // instead; the millisecond of overlap is deduplicated by the store
+    let end = state.until + Duration::milliseconds(1);
+    let mut next_token: Option<String> = None;
+
+    loop {
+        let page: Page<T> = api.fetch_page(T::ENDPOINT, history_start(), end, next_token.as_deref())
+            .await
+            .with_context(|| format!("Backfill of {} stopped; run it again to resume", T::TABLE))?;
+        let next_page = page.next_page(next_token.as_deref());
+
+        state.fetched += page.records.len();
+        state.written += store.upsert(&page.records)?;
+        if let Some(oldest) = page.records.iter().map(Record::start).min() {
+            state.until = state.until.min(oldest);
+        }
+        state.done = next_page.is_none();
+        checkpoint.collections.insert(T::SECTION, state);
+        store.save_backfill_checkpoint(checkpoint)?;
+
+        progress(T::SECTION, &state);
+        if state.done {
+            return Ok(());
+        }
+
+        next_token = next_page;
+        api.wait_for_rate_limit().await;
+    }
+}
@@ -1,63 +1,468 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 use crate::auth::Tokens;
 use crate::data::DashboardData;
+use crate::format;
+use crate::store::Retention;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+const DEFAULT_API_BASE: &str = "https://api.prod.whoop.com/developer";
+const DEFAULT_AUTH_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/auth";
+const DEFAULT_TOKEN_URL: &str = "https://api.prod.whoop.com/oauth/oauth2/token";
+const DEFAULT_REDIRECT_URI: &str = "http://localhost:8080/callback";
+const DEFAULT_CALLBACK_ADDRESS: &str = "127.0.0.1";
+const DEFAULT_HISTORY_DAYS: u32 = 7;
+/// Minutes a cached dashboard is shown before it's refreshed
+const DEFAULT_CACHE_TTL: u64 = 30;
+/// Seconds `--auth` waits for the browser to come back with a login
+const DEFAULT_LOGIN_TIMEOUT: u64 = 300;
+/// The profile whose data lives directly in the data directory, as it did
+/// before there were profiles
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The environment variable that sets each setting.
+const ENV_VARS: &[(&str, &str)] = &[
+    ("client_id", "WHOOP_CLIENT_ID"),
+    ("client_secret", "WHOOP_CLIENT_SECRET"),
+    ("history_days", "WHOOP_HISTORY_DAYS"),
+    ("cache_ttl", "WHOOP_CACHE_TTL"),
+    ("api_base", "WHOOP_API_BASE"),
+    ("auth_url", "WHOOP_AUTH_URL"),
+    ("token_url", "WHOOP_TOKEN_URL"),
+    ("redirect_uri", "WHOOP_REDIRECT_URI"),
+    ("callback_address", "WHOOP_CALLBACK_ADDRESS"),
+    ("callback_port", "WHOOP_CALLBACK_PORT"),
+    ("login_timeout", "WHOOP_LOGIN_TIMEOUT"),
+    ("data_dir", "WHOOP_DATA_DIR"),
+    ("profile", "WHOOP_PROFILE"),
+    ("retention", "WHOOP_RETENTION"),
+];
+
+/// Files in the data directory only the user may read: the OAuth tokens, and
+/// the health data in the cache and history store.
+const PRIVATE_FILES: &[&str] = &["tokens.json", "cache.json", "history.db"];
+
+/// Where a setting's effective value came from, lowest precedence first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    /// The selected profile's section of the config file
+    Profile(String, PathBuf),
+    Env(&'static str),
+    Cli,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Profile(name, path) => write!(f, "profile {} in {}", name, path.display()),
+            Origin::Env(var) => write!(f, "${}", var),
+            Origin::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// Settings, layered from built-in defaults, `config.json` in the platform
+/// config directory, `WHOOP_*` environment variables and command-line flags,
+/// each overriding the ones before it. The file uses the field names below.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub client_id: Option<String>,
     pub client_secret: Option<String>,
+    /// Default number of days of history to show
+    pub history_days: Option<u32>,
+    /// Minutes before a cached dashboard is refreshed on startup
+    pub cache_ttl: Option<u64>,
+    /// Overrides for the WHOOP endpoints, e.g. to point at a local stub server
+    pub api_base: Option<String>,
+    pub auth_url: Option<String>,
+    pub token_url: Option<String>,
+    pub redirect_uri: Option<String>,
+    /// Where the login callback listener binds; the port defaults to the
+    /// redirect URI's, but differs when e.g. a tunnel forwards to it
+    pub callback_address: Option<String>,
+    pub callback_port: Option<u16>,
+    /// Seconds to wait for the login callback before giving up
+    pub login_timeout: Option<u64>,
+    /// Overrides the platform data directory
+    pub data_dir: Option<PathBuf>,
+    /// Account profile to use; each has its own tokens and data
+    pub profile: Option<String>,
+    /// Per-profile credentials, which take precedence over the top-level ones
+    /// in the file but not over the environment or flags
+    pub profiles: Option<BTreeMap<String, ProfileConfig>>,
+    /// How much history to keep; older records are pruned after every sync
+    pub retention: Option<Retention>,
+    /// Where each set field came from; fields missing here are defaults
+    #[serde(skip)]
+    pub origins: BTreeMap<String, Origin>,
+}
+
+/// Settings of one account profile in the config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// The number in environment variable `var`, if it's set. Like the config
+/// file, a value that doesn't parse is an error rather than ignored.
+fn env_number<T>(env: impl Fn(&str) -> Option<String>, var: &str, expected: &str, min: T) -> Result<Option<T>>
+where
+    T: std::str::FromStr + PartialOrd,
+{
+    let Some(value) = env(var) else {
+        return Ok(None);
+    };
+    match value.trim().parse() {
+        Ok(number) if number >= min => Ok(Some(number)),
+        _ => anyhow::bail!("Invalid {}={:?}: expected {}", var, value, expected),
+    }
+}
+
+/// `config.json` in the platform config directory, e.g. `~/.config/whoopterm/`.
+pub fn config_file() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("whoopterm").join("config.json"))
 }
 
 impl Config {
-    pub fn load() -> Self {
-        // Try to load from environment variables first
-        let client_id = std::env::var("WHOOP_CLIENT_ID").ok();
-        let client_secret = std::env::var("WHOOP_CLIENT_SECRET").ok();
+    /// Load the configuration, with `overrides` (e.g. from command-line
+    /// flags) taking precedence over everything else.
+    pub fn load(overrides: &Config) -> Result<Self> {
+        Self::load_from(config_file().as_deref(), |var| std::env::var(var).ok(), overrides)
+    }
+
+    /// Layer the defaults, the config file at `file` (if it exists) with the
+    /// section of whichever profile is selected, the environment as read by
+    /// `env`, and `overrides`.
+    pub fn load_from(file: Option<&Path>, env: impl Fn(&str) -> Option<String>, overrides: &Config) -> Result<Self> {
+        let mut config = Config {
+            history_days: Some(DEFAULT_HISTORY_DAYS),
+            cache_ttl: Some(DEFAULT_CACHE_TTL),
+            api_base: Some(DEFAULT_API_BASE.to_string()),
+            auth_url: Some(DEFAULT_AUTH_URL.to_string()),
+            token_url: Some(DEFAULT_TOKEN_URL.to_string()),
+            redirect_uri: Some(DEFAULT_REDIRECT_URI.to_string()),
+            callback_address: Some(DEFAULT_CALLBACK_ADDRESS.to_string()),
+            login_timeout: Some(DEFAULT_LOGIN_TIMEOUT),
+            data_dir: dirs::data_dir().map(|dir| dir.join("whoopterm")),
+            ..Default::default()
+        };
+
+        let from_file = match file.filter(|path| path.exists()) {
+            Some(path) => {
+                let json = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let from_file: Config = serde_json::from_str(&json)
+                    .with_context(|| format!("Invalid config file {}", path.display()))?;
+                Some((path, from_file))
+            }
+            None => None,
+        };
+
+        let from_env = Config {
+            client_id: env("WHOOP_CLIENT_ID"),
+            client_secret: env("WHOOP_CLIENT_SECRET"),
+            history_days: env_number(&env, "WHOOP_HISTORY_DAYS", "a whole number of days, at least 1", 1)?,
+            cache_ttl: env_number(&env, "WHOOP_CACHE_TTL", "a whole number of minutes", 0)?,
+            api_base: env("WHOOP_API_BASE"),
+            auth_url: env("WHOOP_AUTH_URL"),
+            token_url: env("WHOOP_TOKEN_URL"),
+            redirect_uri: env("WHOOP_REDIRECT_URI"),
+            callback_address: env("WHOOP_CALLBACK_ADDRESS"),
+            callback_port: env_number(&env, "WHOOP_CALLBACK_PORT", "a port number (0-65535)", 0)?,
+            login_timeout: env_number(&env, "WHOOP_LOGIN_TIMEOUT", "a whole number of seconds, at least 1", 1)?,
+            data_dir: env("WHOOP_DATA_DIR").map(PathBuf::from),
+            profile: env("WHOOP_PROFILE"),
+            retention: env("WHOOP_RETENTION")
+                .map(|v| v.parse().map_err(|e| anyhow::anyhow!("Invalid WHOOP_RETENTION: {}", e)))
+                .transpose()?,
+            ..Default::default()
+        };
+
+        // The profile is picked by the highest layer that names one, but its
+        // section belongs to the file layer
+        let name = overrides.profile.clone()
+            .or_else(|| from_env.profile.clone())
+            .or_else(|| from_file.as_ref().and_then(|(_, file)| file.profile.clone()))
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        if !is_valid_profile_name(&name) {
+            anyhow::bail!("Invalid profile name {:?}: use letters, digits, '-' and '_'", name);
+        }
+
+        if let Some((path, from_file)) = from_file {
+            config.layer(&from_file, |_| Origin::File(path.to_path_buf()))?;
+            let section = from_file.profiles.as_ref().and_then(|profiles| profiles.get(&name)).cloned();
+            if let Some(section) = section {
+                let settings = Config {
+                    client_id: section.client_id,
+                    client_secret: section.client_secret,
+                    ..Default::default()
+                };
+                config.layer(&settings, |_| Origin::Profile(name.clone(), path.to_path_buf()))?;
+            }
+        }
+        config.layer(&from_env, |key| {
+            let var = ENV_VARS.iter().find(|(field, _)| *field == key).map(|(_, var)| *var);
+            var.map_or(Origin::Default, Origin::Env)
+        })?;
+        config.layer(overrides, |_| Origin::Cli)?;
+        Ok(config)
+    }
+
+    fn layer(&mut self, layer: &Config, origin: impl Fn(&str) -> Origin) -> Result<()> {
+        let Value::Object(mut fields) = serde_json::to_value(&*self)? else {
+            unreachable!("Config serializes to an object");
+        };
+        let Value::Object(overrides) = serde_json::to_value(layer)? else {
+            unreachable!("Config serializes to an object");
+        };
+        for (key, value) in overrides.into_iter().filter(|(_, value)| !value.is_null()) {
+            self.origins.insert(key.clone(), origin(&key));
+            fields.insert(key, value);
+        }
+
+        let origins = std::mem::take(&mut self.origins);
+        *self = serde_json::from_value(Value::Object(fields))?;
+        self.origins = origins;
+        Ok(())
+    }
+
+    /// Every setting with its effective value (`None` when unset) and origin.
+    pub fn settings(&self) -> Vec<(String, Option<String>, Origin)> {
+        let Ok(Value::Object(fields)) = serde_json::to_value(self) else {
+            return Vec::new();
+        };
+        fields
+            .into_iter()
+            .filter(|(key, _)| key != "profiles")
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::Null => None,
+                    Value::String(s) => Some(s),
+                    other => Some(other.to_string()),
+                };
+                let origin = self.origins.get(&key).cloned().unwrap_or(Origin::Default);
+                (key, value, origin)
+            })
+            .collect()
+    }
+
+    pub fn cache_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.cache_ttl.unwrap_or(DEFAULT_CACHE_TTL) as i64)
+    }
+
+    pub fn api_base(&self) -> &str {
+        self.api_base.as_deref().unwrap_or(DEFAULT_API_BASE).trim_end_matches('/')
+    }
+
+    pub fn auth_url(&self) -> &str {
+        self.auth_url.as_deref().unwrap_or(DEFAULT_AUTH_URL)
+    }
+
+    pub fn token_url(&self) -> &str {
+        self.token_url.as_deref().unwrap_or(DEFAULT_TOKEN_URL)
+    }
+
+    pub fn redirect_uri(&self) -> &str {
+        self.redirect_uri.as_deref().unwrap_or(DEFAULT_REDIRECT_URI)
+    }
+
+    /// Address and port the login callback listener binds to.
+    pub fn callback_bind(&self) -> Result<(String, u16)> {
+        let address = self.callback_address.as_deref().unwrap_or(DEFAULT_CALLBACK_ADDRESS);
+        let port = match self.callback_port {
+            Some(port) => port,
+            None => oauth2::url::Url::parse(self.redirect_uri())
+                .with_context(|| format!("Invalid redirect URI {}", self.redirect_uri()))?
+                .port_or_known_default()
+                .with_context(|| format!("Redirect URI {} has no port", self.redirect_uri()))?,
+        };
+        Ok((address.to_string(), port))
+    }
+
+    pub fn login_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.login_timeout.unwrap_or(DEFAULT_LOGIN_TIMEOUT))
+    }
+
+    pub fn profile(&self) -> &str {
+        self.profile.as_deref().unwrap_or(DEFAULT_PROFILE)
+    }
 
-        Config {
-            client_id,
-            client_secret,
+    /// The data directory shared by all profiles.
+    fn base_data_dir(&self) -> Result<PathBuf> {
+        match &self.data_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(dirs::data_dir()
+                .context("Failed to get data directory")?
+                .join("whoopterm")),
         }
     }
 
-    pub fn data_dir() -> Result<PathBuf> {
-        let dir = dirs::data_dir()
-            .context("Failed to get data directory")?
-            .join("whoopterm");
-        
-        fs::create_dir_all(&dir)?;
+    /// Where `profile` keeps its tokens and data. The default profile uses the
+    /// data directory itself, the others a folder under `profiles/`.
+    pub fn profile_dir(&self, profile: &str) -> Result<PathBuf> {
+        let base = self.base_data_dir()?;
+        Ok(match profile {
+            DEFAULT_PROFILE => base,
+            name => base.join("profiles").join(name),
+        })
+    }
+
+    fn data_dir_path(&self) -> Result<PathBuf> {
+        self.profile_dir(self.profile())
+    }
+
+    /// The selected profile's data directory, created private to the user if
+    /// it doesn't exist.
+    pub fn data_dir(&self) -> Result<PathBuf> {
+        let dir = self.data_dir_path()?;
+        if !dir.exists() {
+            // Directories above the data directory aren't ours to restrict
+            if let Some(parent) = self.base_data_dir()?.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // The data directory, and for other profiles `profiles/` and the
+            // profile's own, are all private
+            let mut builder = fs::DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+            builder.create(&dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
         Ok(dir)
     }
 
+    /// Every known profile: the default one, those configured in the config
+    /// file and those with data on disk, sorted by name.
+    pub fn profile_names(&self) -> Result<Vec<String>> {
+        let mut names: std::collections::BTreeSet<String> = self.profiles.iter().flatten().map(|(name, _)| name.clone()).collect();
+        names.insert(DEFAULT_PROFILE.to_string());
+        names.insert(self.profile().to_string());
+        if let Ok(entries) = fs::read_dir(self.base_data_dir()?.join("profiles")) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if entry.path().is_dir() && is_valid_profile_name(&name) {
+                    names.insert(name);
+                }
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    /// Warnings for an existing data directory or data files that other local
+    /// users can access.
+    pub fn permission_warnings(&self) -> Vec<String> {
+        let Ok(dir) = self.data_dir_path() else {
+            return Vec::new();
+        };
+        let mut warnings = Vec::new();
+        let paths = std::iter::once((dir.clone(), 0o700)).chain(PRIVATE_FILES.iter().map(|f| (dir.join(f), 0o600)));
+        for (path, expected) in paths {
+            if let Some(mode) = group_or_world_access(&path) {
+                warnings.push(format!(
+                    "{} is accessible by other users (mode {:o}); run: chmod {:o} {}",
+                    path.display(), mode, expected, path.display(),
+                ));
+            }
+        }
+        if let Some(Origin::File(path) | Origin::Profile(_, path)) = self.origins.get("client_secret") {
+            if let Some(mode) = group_or_world_access(path) {
+                warnings.push(format!(
+                    "{} holds the client secret but is accessible by other users (mode {:o}); run: chmod 600 {}",
+                    path.display(), mode, path.display(),
+                ));
+            }
+        }
+        warnings
+    }
+
     pub fn save_tokens(&self, tokens: &Tokens) -> Result<()> {
-        let path = Self::data_dir()?.join("tokens.json");
-        let json = serde_json::to_string_pretty(tokens)?;
-        fs::write(path, json)?;
-        Ok(())
+        let path = self.data_dir()?.join("tokens.json");
+        write_private(&path, &format::TOKENS.encode(tokens)?)
     }
 
     pub fn load_tokens(&self) -> Result<Tokens> {
-        let path = Self::data_dir()?.join("tokens.json");
+        let path = self.data_dir()?.join("tokens.json");
         let json = fs::read_to_string(path)?;
-        let tokens: Tokens = serde_json::from_str(&json)?;
-        Ok(tokens)
+        format::TOKENS.decode(&json)
     }
 
     pub fn save_cache(&self, data: &DashboardData) -> Result<()> {
-        let path = Self::data_dir()?.join("cache.json");
-        let json = serde_json::to_string_pretty(data)?;
-        fs::write(path, json)?;
-        Ok(())
+        let path = self.data_dir()?.join("cache.json");
+        write_private(&path, &format::CACHE.encode(data)?)
     }
 
     pub fn load_cache(&self) -> Result<DashboardData> {
-        let path = Self::data_dir()?.join("cache.json");
+        let path = self.data_dir()?.join("cache.json");
         let json = fs::read_to_string(path)?;
-        let data: DashboardData = serde_json::from_str(&json)?;
-        Ok(data)
+        format::CACHE.decode(&json)
     }
 }
+
+fn is_valid_profile_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Replace `path` with `contents` without ever leaving a partly written file:
+/// write a temporary file next to it, readable only by the user, flush it to
+/// disk and rename it over the original.
+pub fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let name = path.file_name().context("Not a file path")?.to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let written = options.open(&temp).and_then(|mut file| {
+        // A leftover temp file from a crash keeps its old mode otherwise
+        restrict(&temp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&temp, path)) {
+        let _ = fs::remove_file(&temp);
+        return Err(e).with_context(|| format!("Failed to write {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Make an existing file readable and writable only by the user.
+pub fn restrict(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// The permission bits of `path` if it exists and grants any access beyond
+/// its owner.
+#[cfg(unix)]
+fn group_or_world_access(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path).ok()?.permissions().mode() & 0o777;
+    (mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn group_or_world_access(_path: &Path) -> Option<u32> {
+    None
+}
@@ -1,5 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 // ── Profile ─────────────────────────────────────────────
 
@@ -11,6 +12,25 @@ pub struct Profile {
     pub last_name: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyMeasurement {
+    pub height_meter: f64,
+    pub weight_kilogram: f64,
+    /// Max heart rate as calculated by WHOOP
+    pub max_heart_rate: i32,
+}
+
+impl BodyMeasurement {
+    /// Express a heart rate as a percentage of the user's max heart rate.
+    pub fn percent_of_max_hr(&self, heart_rate: i32) -> Option<f64> {
+        if self.max_heart_rate > 0 {
+            Some(heart_rate as f64 / self.max_heart_rate as f64 * 100.0)
+        } else {
+            None
+        }
+    }
+}
+
 // ── Recovery ────────────────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,13 +149,249 @@ pub struct ZoneDurations {
     pub zone_five_milli: i64,
 }
 
-// ── Dashboard aggregate ─────────────────────────────────
+// ── Cycle ───────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cycle {
+    pub id: i64,
+    pub user_id: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub start: DateTime<Utc>,
+    /// Absent while the user is still in this cycle
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    pub timezone_offset: String,
+    pub score_state: String,
+    pub score: Option<CycleScore>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CycleScore {
+    pub strain: f64,
+    pub kilojoule: f64,
+    pub average_heart_rate: i32,
+    pub max_heart_rate: i32,
+}
+
+// ── Journal ─────────────────────────────────────────────
+
+/// One answer from the daily journal, e.g. whether the user had any alcohol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Start of the cycle the answer is about
+    pub cycle_start: DateTime<Utc>,
+    pub timezone_offset: String,
+    pub question: String,
+    pub answered_yes: bool,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+// ── Days ────────────────────────────────────────────────
+
+/// Cycles start when the user falls asleep, so the day a cycle is named after
+/// is the one that begins a few hours after its start.
+const CYCLE_DAY_OFFSET: i64 = 12;
+
+/// How far a journal answer's cycle start may be from the synced cycle's, since
+/// answers come from the export and cycles may come from the API.
+const JOURNAL_MATCH_WINDOW: i64 = 60;
+
+/// Everything recorded for one physiological cycle, which WHOOP treats as a day.
+#[derive(Debug, Clone)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub cycle: Option<Cycle>,
+    pub recovery: Option<Recovery>,
+    pub sleep: Option<Sleep>,
+    pub journal: Vec<JournalEntry>,
+}
+
+impl Day {
+    fn new(cycle_start: DateTime<Utc>) -> Self {
+        Day {
+            date: (cycle_start + Duration::hours(CYCLE_DAY_OFFSET)).with_timezone(&Local).date_naive(),
+            cycle: None,
+            recovery: None,
+            sleep: None,
+            journal: Vec::new(),
+        }
+    }
+}
+
+// ── History window ──────────────────────────────────────
+
+/// The span of history the dashboard fetches and displays.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryWindow {
+    /// The last `n` days up to now
+    Days(u32),
+    /// A fixed range; an open `until` runs up to now
+    Range {
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    },
+}
+
+impl Default for HistoryWindow {
+    fn default() -> Self {
+        HistoryWindow::Days(7)
+    }
+}
+
+impl HistoryWindow {
+    /// Build a range from local calendar dates, `until` being inclusive.
+    pub fn from_dates(since: NaiveDate, until: Option<NaiveDate>) -> Self {
+        HistoryWindow::Range {
+            since: local_midnight(since),
+            until: until.map(|d| local_midnight(d + Duration::days(1))),
+        }
+    }
+
+    /// Resolve the window to concrete `(start, end)` bounds.
+    pub fn bounds(&self) -> (DateTime<Utc>, DateTime<Utc>) {
+        let now = Utc::now();
+        match *self {
+            HistoryWindow::Days(days) => (now - Duration::days(days as i64), now),
+            HistoryWindow::Range { since, until } => (since, until.unwrap_or(now)),
+        }
+    }
+
+    /// Whether the window runs up to now rather than ending at a fixed date.
+    pub fn is_open(&self) -> bool {
+        matches!(self, HistoryWindow::Days(_) | HistoryWindow::Range { until: None, .. })
+    }
+
+    /// Short label for panel titles, e.g. `7d` or `Mar 01 – Mar 14`.
+    pub fn label(&self) -> String {
+        match *self {
+            HistoryWindow::Days(days) => format!("{}d", days),
+            HistoryWindow::Range { since, until } => {
+                let until = until
+                    .map(|u| (u - Duration::days(1)).with_timezone(&Local).format("%b %d").to_string())
+                    .unwrap_or_else(|| "now".to_string());
+                format!("{} – {}", since.with_timezone(&Local).format("%b %d"), until)
+            }
+        }
+    }
+}
+
+fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| midnight.and_utc())
+}
+
+// ── Dashboard aggregate ─────────────────────────────────
+
+/// Parts of the dashboard that are fetched (and can fail) independently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Section {
+    Profile,
+    Body,
+    Recovery,
+    Sleep,
+    Workouts,
+    Cycles,
+    /// Journal answers, which the API doesn't serve and so aren't in `ALL`
+    Journal,
+}
+
+impl Section {
+    pub const ALL: [Section; 6] = [
+        Section::Profile,
+        Section::Body,
+        Section::Recovery,
+        Section::Sleep,
+        Section::Workouts,
+        Section::Cycles,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Profile => "profile",
+            Section::Body => "body",
+            Section::Recovery => "recovery",
+            Section::Sleep => "sleep",
+            Section::Workouts => "workouts",
+            Section::Cycles => "cycles",
+            Section::Journal => "journal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DashboardData {
     pub profile: Option<Profile>,
+    #[serde(default)]
+    pub body: Option<BodyMeasurement>,
     pub recovery: Vec<Recovery>,
     pub sleep: Vec<Sleep>,
     pub workouts: Vec<Workout>,
+    #[serde(default)]
+    pub cycles: Vec<Cycle>,
+    #[serde(default)]
+    pub journal: Vec<JournalEntry>,
+    /// Window the data was fetched for; caches from before this field were 7 days
+    #[serde(default)]
+    pub window: HistoryWindow,
     pub refreshed_at: Option<DateTime<Utc>>,
+    /// When each section was last fetched from the API
+    #[serde(default)]
+    pub fetched_at: BTreeMap<Section, DateTime<Utc>>,
+    /// Sections whose last fetch failed; never persisted
+    #[serde(skip)]
+    pub errors: BTreeMap<Section, String>,
+    /// Why the data was read from disk instead of fetched, if it was
+    #[serde(skip)]
+    pub offline: Option<String>,
+}
+
+impl DashboardData {
+    /// Whether any section fetched from the API succeeded.
+    pub fn fetched_any(&self) -> bool {
+        Section::ALL.iter().any(|section| !self.errors.contains_key(section))
+    }
+
+    /// When the least recently fetched section was fetched, or `None` if one
+    /// never has been; the dashboard is only as fresh as this.
+    pub fn oldest_fetch(&self) -> Option<DateTime<Utc>> {
+        Section::ALL.iter().map(|section| self.fetched_at.get(section).copied()).min().flatten()
+    }
+
+    /// The window's cycles with their recovery, sleep and journal answers,
+    /// newest first. Answers for cycles that weren't synced get a day of their own.
+    pub fn days(&self) -> Vec<Day> {
+        let mut days: Vec<(DateTime<Utc>, Day)> = self.cycles
+            .iter()
+            .map(|cycle| {
+                let mut day = Day::new(cycle.start);
+                day.recovery = self.recovery.iter().find(|r| r.cycle_id == cycle.id).cloned();
+                day.sleep = self.sleep.iter().find(|s| s.cycle_id == cycle.id).cloned();
+                day.cycle = Some(cycle.clone());
+                (cycle.start, day)
+            })
+            .collect();
+
+        for entry in &self.journal {
+            let near = |start: &DateTime<Utc>| (*start - entry.cycle_start).num_minutes().abs() <= JOURNAL_MATCH_WINDOW;
+            match days.iter_mut().find(|(start, _)| near(start)) {
+                Some((_, day)) => day.journal.push(entry.clone()),
+                None => {
+                    let mut day = Day::new(entry.cycle_start);
+                    day.journal.push(entry.clone());
+                    days.push((entry.cycle_start, day));
+                }
+            }
+        }
+
+        days.sort_by_key(|(start, _)| std::cmp::Reverse(*start));
+        days.into_iter().map(|(_, day)| day).collect()
+    }
 }
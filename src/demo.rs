@@ -0,0 +1,246 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::data::{
+    BodyMeasurement, Cycle, CycleScore, JournalEntry, Profile, Recovery, RecoveryScore, Sleep, SleepNeeded, SleepScore,
+    SleepStageSummary, Workout, WorkoutScore, ZoneDurations,
+};
+use crate::source::DataSource;
+
+const DEMO_USER_ID: i64 = 1;
+const SPORTS: [&str; 5] = ["running", "cycling", "weightlifting", "swimming", "yoga"];
+/// Journal questions with how often the demo user answers yes
+const JOURNAL: [(&str, f64); 4] = [
+    ("Have any alcoholic drinks?", 0.25),
+    ("Have any caffeine?", 0.8),
+    ("Eat food close to bedtime?", 0.3),
+    ("Read (non-screen device) while in bed?", 0.5),
+];
+
+/// Plausible, deterministic data for demos and for driving the UI without an
+/// account. Each day is generated from its own seed, so refreshing (or moving
+/// the window) never changes a day that was already shown.
+pub struct DemoSource;
+
+/// Everything generated for one physiological day.
+struct DemoDay {
+    cycle: Cycle,
+    sleep: Sleep,
+    recovery: Recovery,
+    workout: Option<Workout>,
+    journal: Vec<JournalEntry>,
+}
+
+impl DemoSource {
+    /// Days whose wake-up falls within `[start, end)`, newest first.
+    fn days(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<DemoDay> {
+        let now = Utc::now();
+        let end = end.min(now);
+        let mut day = end.duration_trunc(Duration::days(1)).unwrap_or(end);
+        let mut days = Vec::new();
+
+        while day + Duration::hours(7) >= start {
+            let generated = generate_day(day, now);
+            if generated.sleep.end >= start && generated.sleep.end < end {
+                days.push(generated);
+            }
+            day -= Duration::days(1);
+        }
+        days
+    }
+}
+
+/// The day's seeded generator, already past drawing when the demo user
+/// wakes up that day.
+fn day_rng(day: DateTime<Utc>) -> (StdRng, DateTime<Utc>) {
+    let mut rng = StdRng::seed_from_u64((day.timestamp() / 86_400) as u64);
+    let wake = day + Duration::hours(7) + Duration::minutes(rng.gen_range(-45..45));
+    (rng, wake)
+}
+
+fn generate_day(day: DateTime<Utc>, now: DateTime<Utc>) -> DemoDay {
+    let day_number = day.timestamp() / 86_400;
+    let (mut rng, wake) = day_rng(day);
+    // Cycles run from one wake-up to the next, with no gaps or overlaps
+    let (_, next_wake) = day_rng(day + Duration::days(1));
+    let in_bed = Duration::minutes(rng.gen_range(330..540));
+    let sleep_start = wake - in_bed;
+    let timezone_offset = "Z".to_string();
+
+    let in_bed_milli = in_bed.num_milliseconds();
+    let awake = in_bed_milli * rng.gen_range(5..12) / 100;
+    let deep = in_bed_milli * rng.gen_range(15..22) / 100;
+    let rem = in_bed_milli * rng.gen_range(18..26) / 100;
+    let light = in_bed_milli - awake - deep - rem;
+
+    let sleep_id = format!("demo-sleep-{}", day_number);
+    let sleep = Sleep {
+        id: sleep_id.clone(),
+        cycle_id: day_number,
+        user_id: DEMO_USER_ID,
+        created_at: wake,
+        updated_at: wake,
+        start: sleep_start,
+        end: wake,
+        timezone_offset: timezone_offset.clone(),
+        nap: false,
+        score_state: "SCORED".to_string(),
+        score: Some(SleepScore {
+            stage_summary: SleepStageSummary {
+                total_in_bed_time_milli: in_bed_milli,
+                total_awake_time_milli: awake,
+                total_no_data_time_milli: 0,
+                total_light_sleep_time_milli: light,
+                total_slow_wave_sleep_time_milli: deep,
+                total_rem_sleep_time_milli: rem,
+                sleep_cycle_count: rng.gen_range(3..6),
+                disturbance_count: rng.gen_range(4..18),
+            },
+            sleep_needed: SleepNeeded {
+                baseline_milli: 27_000_000,
+                need_from_sleep_debt_milli: rng.gen_range(0..3_600_000),
+                need_from_recent_strain_milli: rng.gen_range(0..1_800_000),
+                need_from_recent_nap_milli: 0,
+            },
+            respiratory_rate: Some(rng.gen_range(14.0..17.0)),
+            sleep_performance_percentage: Some(rng.gen_range(60.0..100.0)),
+            sleep_consistency_percentage: Some(rng.gen_range(55.0..95.0)),
+            sleep_efficiency_percentage: Some(100.0 - awake as f64 / in_bed_milli as f64 * 100.0),
+        }),
+    };
+
+    let recovery = Recovery {
+        cycle_id: day_number,
+        sleep_id,
+        user_id: DEMO_USER_ID,
+        created_at: wake,
+        updated_at: wake,
+        score_state: "SCORED".to_string(),
+        score: Some(RecoveryScore {
+            recovery_score: rng.gen_range(15.0..99.0_f64).round(),
+            resting_heart_rate: rng.gen_range(48.0..64.0_f64).round(),
+            hrv_rmssd_milli: rng.gen_range(35.0..95.0),
+            user_calibrating: false,
+            spo2_percentage: Some(rng.gen_range(94.0..99.0)),
+            skin_temp_celsius: Some(rng.gen_range(33.0..34.8)),
+        }),
+    };
+
+    let workout = rng.gen_bool(0.6).then(|| {
+        let start = wake + Duration::minutes(rng.gen_range(60..720));
+        let length = Duration::minutes(rng.gen_range(25..95));
+        let zone = |share: i64| length.num_milliseconds() * share / 100;
+        Workout {
+            id: format!("demo-workout-{}", day_number),
+            user_id: DEMO_USER_ID,
+            created_at: start + length,
+            updated_at: start + length,
+            start,
+            end: start + length,
+            timezone_offset: timezone_offset.clone(),
+            sport_name: SPORTS[rng.gen_range(0..SPORTS.len())].to_string(),
+            score_state: "SCORED".to_string(),
+            score: Some(WorkoutScore {
+                strain: rng.gen_range(5.0..17.0),
+                average_heart_rate: rng.gen_range(110..155),
+                max_heart_rate: rng.gen_range(155..188),
+                kilojoule: rng.gen_range(800.0..3200.0),
+                percent_recorded: 100.0,
+                zone_durations: ZoneDurations {
+                    zone_zero_milli: zone(5),
+                    zone_one_milli: zone(20),
+                    zone_two_milli: zone(35),
+                    zone_three_milli: zone(25),
+                    zone_four_milli: zone(10),
+                    zone_five_milli: zone(5),
+                },
+                distance_meter: None,
+                altitude_gain_meter: None,
+                altitude_change_meter: None,
+            }),
+        }
+    }).filter(|w| w.end <= now);
+
+    // Today's cycle is still open
+    let cycle_end = (next_wake <= now).then_some(next_wake);
+    let cycle = Cycle {
+        id: day_number,
+        user_id: DEMO_USER_ID,
+        created_at: wake,
+        updated_at: cycle_end.unwrap_or(now),
+        start: wake,
+        end: cycle_end,
+        timezone_offset,
+        score_state: "SCORED".to_string(),
+        score: Some(CycleScore {
+            strain: rng.gen_range(4.0..18.0),
+            kilojoule: rng.gen_range(7000.0..12000.0),
+            average_heart_rate: rng.gen_range(60..76),
+            max_heart_rate: rng.gen_range(130..185),
+        }),
+    };
+
+    // Answered the morning after, so today's open cycle has none yet
+    let journal = match cycle_end {
+        Some(_) => JOURNAL
+            .iter()
+            .map(|(question, yes)| JournalEntry {
+                cycle_start: wake,
+                timezone_offset: "Z".to_string(),
+                question: question.to_string(),
+                answered_yes: rng.gen_bool(*yes),
+                notes: None,
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+
+    DemoDay { cycle, sleep, recovery, workout, journal }
+}
+
+#[async_trait]
+impl DataSource for DemoSource {
+    fn name(&self) -> &'static str {
+        "demo"
+    }
+
+    async fn profile(&self) -> Result<Profile> {
+        Ok(Profile {
+            user_id: DEMO_USER_ID,
+            email: "demo@example.com".to_string(),
+            first_name: "Demo".to_string(),
+            last_name: "User".to_string(),
+        })
+    }
+
+    async fn body_measurement(&self) -> Result<BodyMeasurement> {
+        Ok(BodyMeasurement {
+            height_meter: 1.78,
+            weight_kilogram: 74.5,
+            max_heart_rate: 192,
+        })
+    }
+
+    async fn recovery(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Recovery>> {
+        Ok(self.days(start, end).into_iter().map(|d| d.recovery).collect())
+    }
+
+    async fn sleep(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Sleep>> {
+        Ok(self.days(start, end).into_iter().map(|d| d.sleep).collect())
+    }
+
+    async fn workouts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Workout>> {
+        Ok(self.days(start, end).into_iter().filter_map(|d| d.workout).collect())
+    }
+
+    async fn cycles(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Cycle>> {
+        Ok(self.days(start, end).into_iter().map(|d| d.cycle).collect())
+    }
+
+    async fn journal(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        Ok(self.days(start, end).into_iter().flat_map(|d| d.journal).collect())
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+/// Key under which a persisted JSON file records its format version. Files
+/// written before versioning don't have it and count as version 0.
+const VERSION_KEY: &str = "format_version";
+
+/// Upgrades a file's JSON from one format version to the next.
+type Migration = fn(&mut Map<String, Value>);
+
+/// The on-disk layout of one persisted JSON file.
+pub struct Format {
+    pub file: &'static str,
+    /// `migrations[n]` upgrades version `n` to `n + 1`, so the current version
+    /// is the number of migrations
+    migrations: &'static [Migration],
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum FormatError {
+    #[error("{file} was written by a newer whoopterm (format version {found}, this one reads up to {supported}). Upgrade whoopterm to use it")]
+    TooNew { file: String, found: u32, supported: u32 },
+}
+
+/// Whether `error` was caused by a file from a newer whoopterm, which must not
+/// be overwritten or silently treated as missing.
+pub fn is_too_new(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.downcast_ref::<FormatError>().is_some())
+}
+
+pub const CACHE: Format = Format {
+    file: "cache.json",
+    migrations: &[cache_v1, cache_v2],
+};
+
+pub const TOKENS: Format = Format {
+    file: "tokens.json",
+    migrations: &[tokens_v1],
+};
+
+/// Unversioned caches may predate the body, cycles, journal and window fields;
+/// spell out what those caches meant (a 7-day window) instead of leaving it to
+/// serde defaults.
+fn cache_v1(data: &mut Map<String, Value>) {
+    data.entry("body").or_insert(Value::Null);
+    data.entry("cycles").or_insert(json!([]));
+    data.entry("journal").or_insert(json!([]));
+    data.entry("window").or_insert(json!({ "days": 7 }));
+}
+
+/// Version 2 tracks when each section was fetched; before that the whole
+/// dashboard was refreshed at once.
+fn cache_v2(data: &mut Map<String, Value>) {
+    let refreshed_at = data.get("refreshed_at").cloned().unwrap_or(Value::Null);
+    let fetched_at: Map<String, Value> = match refreshed_at {
+        Value::Null => Map::new(),
+        at => ["profile", "body", "recovery", "sleep", "workouts", "cycles"]
+            .into_iter()
+            .map(|section| (section.to_string(), at.clone()))
+            .collect(),
+    };
+    data.insert("fetched_at".to_string(), Value::Object(fetched_at));
+}
+
+// Unversioned tokens already have the current fields
+fn tokens_v1(_tokens: &mut Map<String, Value>) {}
+
+impl Format {
+    pub fn version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<String> {
+        let mut json = serde_json::to_value(value)?;
+        let object = json.as_object_mut().context("Only JSON objects can be versioned")?;
+        object.insert(VERSION_KEY.to_string(), self.version().into());
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+
+    /// Parse a file written by this or an older version, migrating it first.
+    pub fn decode<T: DeserializeOwned>(&self, json: &str) -> Result<T> {
+        let mut value: Value = serde_json::from_str(json)
+            .with_context(|| format!("{} is not valid JSON", self.file))?;
+        let object = value.as_object_mut()
+            .with_context(|| format!("{} is not a JSON object", self.file))?;
+
+        let found = match object.remove(VERSION_KEY) {
+            None => 0,
+            Some(version) => version.as_u64()
+                .and_then(|v| u32::try_from(v).ok())
+                .with_context(|| format!("{} has an invalid {}", self.file, VERSION_KEY))?,
+        };
+        if found > self.version() {
+            return Err(FormatError::TooNew {
+                file: self.file.to_string(),
+                found,
+                supported: self.version(),
+            }.into());
+        }
+        for migrate in &self.migrations[found as usize..] {
+            migrate(object);
+        }
+
+        serde_json::from_value(value).with_context(|| format!("Failed to read {}", self.file))
+    }
+}
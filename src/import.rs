@@ -0,0 +1,499 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use crate::data::{
+    Cycle, CycleScore, JournalEntry, Recovery, RecoveryScore, Sleep, SleepNeeded, SleepScore, SleepStageSummary,
+    Workout, WorkoutScore, ZoneDurations,
+};
+use crate::store::{imported_id, Store, Stored};
+
+const CYCLES_CSV: &str = "physiological_cycles.csv";
+const SLEEPS_CSV: &str = "sleeps.csv";
+const WORKOUTS_CSV: &str = "workouts.csv";
+const JOURNAL_CSV: &str = "journal_entries.csv";
+const EXPORT_FILES: [&str; 4] = [CYCLES_CSV, SLEEPS_CSV, WORKOUTS_CSV, JOURNAL_CSV];
+
+const KILOJOULES_PER_CALORIE: f64 = 4.184;
+const MINUTE_MILLIS: f64 = 60_000.0;
+
+/// What an import added, per kind of record, in the order they're imported.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub counts: Vec<(&'static str, ImportCount)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ImportCount {
+    pub imported: usize,
+    /// Rows that were already stored, from the API or an earlier import
+    pub duplicates: usize,
+}
+
+/// Load a WHOOP account export (the unzipped folder or the zip itself) into
+/// `store`. Rows matching a record that's already stored are skipped, so the
+/// API's copy wins and importing the same export twice changes nothing.
+pub fn import(path: &Path, store: &mut Store, user_id: i64) -> Result<ImportReport> {
+    let export = Export::open(path)?;
+    let cycle_rows: Vec<CycleRow> = export.rows(CYCLES_CSV)?;
+    let sleep_rows: Vec<SleepRow> = export.rows(SLEEPS_CSV)?;
+    let workout_rows: Vec<WorkoutRow> = export.rows(WORKOUTS_CSV)?;
+    let journal_rows: Vec<JournalRow> = export.rows(JOURNAL_CSV)?;
+    let mut report = ImportReport::default();
+
+    // Cycles first: sleeps and recoveries point at them, by their API id when
+    // the cycle was already synced
+    let mut cycle_ids = HashMap::new();
+    let mut cycles = Vec::new();
+    for row in &cycle_rows {
+        let cycle = row.cycle(user_id).context("Failed to read physiological_cycles.csv")?;
+        let id = match store.find_near::<Cycle>(cycle.start)? {
+            Some(key) => key.parse().unwrap_or(cycle.id),
+            None => cycle.id,
+        };
+        cycle_ids.insert(row.cycle_start.clone(), id);
+        cycles.push(cycle);
+    }
+    let cycle_id = |cycle_start: &str, time: DateTime<Utc>| {
+        cycle_ids.get(cycle_start).copied().unwrap_or_else(|| imported_id(time))
+    };
+    report.counts.push(("cycles", load(store, cycles)?));
+
+    let sleeps = sleep_rows
+        .iter()
+        .map(|row| {
+            let offset = parse_offset(row.timezone.as_deref())?;
+            let cycle_start = parse_time(&row.cycle_start, offset)?;
+            row.sleep(user_id, cycle_id(&row.cycle_start, cycle_start), offset)
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to read sleeps.csv")?;
+    report.counts.push(("sleep", load(store, sleeps)?));
+
+    let mut recoveries = Vec::new();
+    for row in &cycle_rows {
+        let offset = parse_offset(row.timezone.as_deref())?;
+        let cycle_start = parse_time(&row.cycle_start, offset)?;
+        let sleep_onset = row.sleep_onset.as_deref().map(|t| parse_time(t, offset)).transpose()?;
+        let sleep_id = match sleep_onset {
+            Some(onset) => store.find_near::<Sleep>(onset)?.unwrap_or_else(|| imported_id(onset).to_string()),
+            None => String::new(),
+        };
+        if let Some(recovery) = row.recovery(user_id, cycle_id(&row.cycle_start, cycle_start), sleep_id, offset)? {
+            recoveries.push(recovery);
+        }
+    }
+    report.counts.push(("recovery", load(store, recoveries)?));
+
+    let workouts = workout_rows
+        .iter()
+        .map(|row| row.workout(user_id))
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to read workouts.csv")?;
+    report.counts.push(("workouts", load(store, workouts)?));
+
+    let journal = journal_rows
+        .iter()
+        .map(JournalRow::entry)
+        .collect::<Result<Vec<_>>>()
+        .context("Failed to read journal_entries.csv")?;
+    let imported = store.upsert_journal(&journal)?;
+    report.counts.push(("journal", ImportCount { imported, duplicates: journal.len() - imported }));
+
+    Ok(report)
+}
+
+/// Store the records that aren't stored yet.
+fn load<T: Stored>(store: &mut Store, records: Vec<T>) -> Result<ImportCount> {
+    let mut count = ImportCount::default();
+    let mut fresh = Vec::new();
+    for record in records {
+        if store.find_near::<T>(record.start())?.is_some() {
+            count.duplicates += 1;
+        } else {
+            fresh.push(record);
+        }
+    }
+    count.imported = store.upsert(&fresh)?;
+    Ok(count)
+}
+
+// ── Export files ────────────────────────────────────────
+
+/// The CSVs of an export, by file name.
+struct Export {
+    files: BTreeMap<&'static str, String>,
+}
+
+impl Export {
+    fn open(path: &Path) -> Result<Self> {
+        let mut files = BTreeMap::new();
+
+        if path.is_dir() {
+            for name in EXPORT_FILES {
+                let file = path.join(name);
+                if file.exists() {
+                    let csv = fs::read_to_string(&file)
+                        .with_context(|| format!("Failed to read {}", file.display()))?;
+                    files.insert(name, csv);
+                }
+            }
+        } else {
+            let zip = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+            let mut archive = zip::ZipArchive::new(zip)
+                .with_context(|| format!("{} is neither a folder nor a zip file", path.display()))?;
+            // The CSVs may sit in a folder inside the zip
+            for i in 0..archive.len() {
+                let mut entry = archive.by_index(i)?;
+                let Some(name) = entry.enclosed_name().and_then(|p| p.file_name().map(|n| n.to_owned())) else {
+                    continue;
+                };
+                if let Some(name) = EXPORT_FILES.into_iter().find(|f| name == **f) {
+                    let mut csv = String::new();
+                    entry.read_to_string(&mut csv)
+                        .with_context(|| format!("Failed to read {} from {}", name, path.display()))?;
+                    files.insert(name, csv);
+                }
+            }
+        }
+
+        if files.is_empty() {
+            bail!("No WHOOP export files found in {} (expected {})", path.display(), EXPORT_FILES.join(", "));
+        }
+        Ok(Self { files })
+    }
+
+    /// Rows of one CSV; an export without the file simply has none.
+    fn rows<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>> {
+        let Some(csv) = self.files.get(name) else {
+            return Ok(Vec::new());
+        };
+        csv::Reader::from_reader(csv.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("Failed to parse {}", name))
+    }
+}
+
+/// Parse an export timestamp such as `2024-03-01 22:15:03`, which is local to
+/// the cycle's timezone.
+fn parse_time(value: &str, offset: FixedOffset) -> Result<DateTime<Utc>> {
+    let local = NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S")
+        .with_context(|| format!("Invalid timestamp '{}'", value))?;
+    local
+        .and_local_timezone(offset)
+        .single()
+        .map(|t| t.with_timezone(&Utc))
+        .with_context(|| format!("Invalid timestamp '{}'", value))
+}
+
+/// Parse a cycle timezone such as `UTC-05:00`; a missing one means UTC.
+fn parse_offset(timezone: Option<&str>) -> Result<FixedOffset> {
+    let Some(offset) = timezone.map(|tz| tz.trim().trim_start_matches("UTC")).filter(|tz| !tz.is_empty()) else {
+        return Ok(FixedOffset::east_opt(0).expect("UTC is a valid offset"));
+    };
+    let (sign, rest) = match offset.split_at(1) {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => bail!("Invalid timezone '{}'", offset),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let seconds = hours.parse::<i32>().ok()
+        .zip(minutes.parse::<i32>().ok())
+        .map(|(h, m)| sign * (h * 3600 + m * 60))
+        .with_context(|| format!("Invalid timezone '{}'", offset))?;
+    FixedOffset::east_opt(seconds).with_context(|| format!("Invalid timezone '{}'", offset))
+}
+
+/// The offset in the API's format, e.g. `-05:00`.
+fn offset_string(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, seconds.abs() / 3600, seconds.abs() % 3600 / 60)
+}
+
+fn minutes_to_milli(minutes: Option<f64>) -> i64 {
+    (minutes.unwrap_or(0.0) * MINUTE_MILLIS).round() as i64
+}
+
+fn score_state(scored: bool) -> String {
+    if scored { "SCORED" } else { "UNSCORABLE" }.to_string()
+}
+
+// ── Rows ────────────────────────────────────────────────
+
+#[derive(Deserialize)]
+struct CycleRow {
+    #[serde(rename = "Cycle start time")]
+    cycle_start: String,
+    #[serde(rename = "Cycle end time")]
+    cycle_end: Option<String>,
+    #[serde(rename = "Cycle timezone")]
+    timezone: Option<String>,
+    #[serde(rename = "Recovery score %")]
+    recovery_score: Option<f64>,
+    #[serde(rename = "Resting heart rate (bpm)")]
+    resting_heart_rate: Option<f64>,
+    #[serde(rename = "Heart rate variability (ms)")]
+    hrv: Option<f64>,
+    #[serde(rename = "Skin temp (celsius)")]
+    skin_temp: Option<f64>,
+    #[serde(rename = "Blood oxygen %")]
+    spo2: Option<f64>,
+    #[serde(rename = "Day Strain")]
+    strain: Option<f64>,
+    #[serde(rename = "Energy burned (cal)")]
+    calories: Option<f64>,
+    #[serde(rename = "Max HR (bpm)")]
+    max_heart_rate: Option<f64>,
+    #[serde(rename = "Average HR (bpm)")]
+    average_heart_rate: Option<f64>,
+    #[serde(rename = "Sleep onset")]
+    sleep_onset: Option<String>,
+    #[serde(rename = "Wake onset")]
+    wake_onset: Option<String>,
+}
+
+impl CycleRow {
+    fn cycle(&self, user_id: i64) -> Result<Cycle> {
+        let offset = parse_offset(self.timezone.as_deref())?;
+        let start = parse_time(&self.cycle_start, offset)?;
+        let end = self.cycle_end.as_deref().map(|t| parse_time(t, offset)).transpose()?;
+
+        Ok(Cycle {
+            id: imported_id(start),
+            user_id,
+            created_at: start,
+            updated_at: end.unwrap_or(start),
+            start,
+            end,
+            timezone_offset: offset_string(offset),
+            score_state: score_state(self.strain.is_some()),
+            score: self.strain.map(|strain| CycleScore {
+                strain,
+                kilojoule: self.calories.unwrap_or(0.0) * KILOJOULES_PER_CALORIE,
+                average_heart_rate: self.average_heart_rate.unwrap_or(0.0).round() as i32,
+                max_heart_rate: self.max_heart_rate.unwrap_or(0.0).round() as i32,
+            }),
+        })
+    }
+
+    /// The cycle's recovery, if it was scored.
+    fn recovery(&self, user_id: i64, cycle_id: i64, sleep_id: String, offset: FixedOffset) -> Result<Option<Recovery>> {
+        let Some(recovery_score) = self.recovery_score else {
+            return Ok(None);
+        };
+        let wake = match &self.wake_onset {
+            Some(wake) => parse_time(wake, offset)?,
+            None => parse_time(&self.cycle_start, offset)?,
+        };
+
+        Ok(Some(Recovery {
+            cycle_id,
+            sleep_id,
+            user_id,
+            created_at: wake,
+            updated_at: wake,
+            score_state: score_state(true),
+            score: Some(RecoveryScore {
+                recovery_score,
+                resting_heart_rate: self.resting_heart_rate.unwrap_or(0.0),
+                hrv_rmssd_milli: self.hrv.unwrap_or(0.0),
+                user_calibrating: false,
+                spo2_percentage: self.spo2,
+                skin_temp_celsius: self.skin_temp,
+            }),
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct SleepRow {
+    #[serde(rename = "Cycle start time")]
+    cycle_start: String,
+    #[serde(rename = "Cycle timezone")]
+    timezone: Option<String>,
+    #[serde(rename = "Sleep onset")]
+    onset: String,
+    #[serde(rename = "Wake onset")]
+    wake: String,
+    #[serde(rename = "Sleep performance %")]
+    performance: Option<f64>,
+    #[serde(rename = "Respiratory rate (rpm)")]
+    respiratory_rate: Option<f64>,
+    #[serde(rename = "In bed duration (min)")]
+    in_bed: Option<f64>,
+    #[serde(rename = "Light sleep duration (min)")]
+    light: Option<f64>,
+    #[serde(rename = "Deep (SWS) duration (min)")]
+    deep: Option<f64>,
+    #[serde(rename = "REM duration (min)")]
+    rem: Option<f64>,
+    #[serde(rename = "Awake duration (min)")]
+    awake: Option<f64>,
+    #[serde(rename = "Sleep need (min)")]
+    need: Option<f64>,
+    #[serde(rename = "Sleep debt (min)")]
+    debt: Option<f64>,
+    #[serde(rename = "Sleep efficiency %")]
+    efficiency: Option<f64>,
+    #[serde(rename = "Sleep consistency %")]
+    consistency: Option<f64>,
+    #[serde(rename = "Nap")]
+    nap: Option<bool>,
+}
+
+impl SleepRow {
+    fn sleep(&self, user_id: i64, cycle_id: i64, offset: FixedOffset) -> Result<Sleep> {
+        let start = parse_time(&self.onset, offset)?;
+        let end = parse_time(&self.wake, offset)?;
+        let debt = minutes_to_milli(self.debt);
+
+        Ok(Sleep {
+            id: imported_id(start).to_string(),
+            cycle_id,
+            user_id,
+            created_at: end,
+            updated_at: end,
+            start,
+            end,
+            timezone_offset: offset_string(offset),
+            nap: self.nap.unwrap_or(false),
+            score_state: score_state(self.in_bed.is_some()),
+            score: self.in_bed.map(|in_bed| SleepScore {
+                stage_summary: SleepStageSummary {
+                    total_in_bed_time_milli: minutes_to_milli(Some(in_bed)),
+                    total_awake_time_milli: minutes_to_milli(self.awake),
+                    total_no_data_time_milli: 0,
+                    total_light_sleep_time_milli: minutes_to_milli(self.light),
+                    total_slow_wave_sleep_time_milli: minutes_to_milli(self.deep),
+                    total_rem_sleep_time_milli: minutes_to_milli(self.rem),
+                    // Not part of the export
+                    sleep_cycle_count: 0,
+                    disturbance_count: 0,
+                },
+                sleep_needed: SleepNeeded {
+                    baseline_milli: (minutes_to_milli(self.need) - debt).max(0),
+                    need_from_sleep_debt_milli: debt,
+                    need_from_recent_strain_milli: 0,
+                    need_from_recent_nap_milli: 0,
+                },
+                respiratory_rate: self.respiratory_rate,
+                sleep_performance_percentage: self.performance,
+                sleep_consistency_percentage: self.consistency,
+                sleep_efficiency_percentage: self.efficiency,
+            }),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct WorkoutRow {
+    #[serde(rename = "Cycle timezone")]
+    timezone: Option<String>,
+    #[serde(rename = "Workout start time")]
+    start: String,
+    #[serde(rename = "Workout end time")]
+    end: String,
+    #[serde(rename = "Activity name")]
+    activity: String,
+    #[serde(rename = "Activity Strain")]
+    strain: Option<f64>,
+    #[serde(rename = "Energy burned (cal)")]
+    calories: Option<f64>,
+    #[serde(rename = "Max HR (bpm)")]
+    max_heart_rate: Option<f64>,
+    #[serde(rename = "Average HR (bpm)")]
+    average_heart_rate: Option<f64>,
+    #[serde(rename = "HR Zone 1 %")]
+    zone_one: Option<f64>,
+    #[serde(rename = "HR Zone 2 %")]
+    zone_two: Option<f64>,
+    #[serde(rename = "HR Zone 3 %")]
+    zone_three: Option<f64>,
+    #[serde(rename = "HR Zone 4 %")]
+    zone_four: Option<f64>,
+    #[serde(rename = "HR Zone 5 %")]
+    zone_five: Option<f64>,
+    #[serde(rename = "Distance (meters)", default)]
+    distance: Option<f64>,
+    #[serde(rename = "Altitude gain (meters)", default)]
+    altitude_gain: Option<f64>,
+    #[serde(rename = "Altitude change (meters)", default)]
+    altitude_change: Option<f64>,
+}
+
+impl WorkoutRow {
+    fn workout(&self, user_id: i64) -> Result<Workout> {
+        let offset = parse_offset(self.timezone.as_deref())?;
+        let start = parse_time(&self.start, offset)?;
+        let end = parse_time(&self.end, offset)?;
+        let length = (end - start).num_milliseconds() as f64;
+        let zone = |percent: Option<f64>| (length * percent.unwrap_or(0.0) / 100.0).round() as i64;
+        let zones = [self.zone_one, self.zone_two, self.zone_three, self.zone_four, self.zone_five];
+        let in_zones: i64 = zones.iter().map(|p| zone(*p)).sum();
+
+        Ok(Workout {
+            id: imported_id(start).to_string(),
+            user_id,
+            created_at: end,
+            updated_at: end,
+            start,
+            end,
+            timezone_offset: offset_string(offset),
+            sport_name: self.activity.trim().to_lowercase(),
+            score_state: score_state(self.strain.is_some()),
+            score: self.strain.map(|strain| WorkoutScore {
+                strain,
+                average_heart_rate: self.average_heart_rate.unwrap_or(0.0).round() as i32,
+                max_heart_rate: self.max_heart_rate.unwrap_or(0.0).round() as i32,
+                kilojoule: self.calories.unwrap_or(0.0) * KILOJOULES_PER_CALORIE,
+                percent_recorded: 100.0,
+                zone_durations: ZoneDurations {
+                    // Whatever isn't in zones 1-5 was spent below them
+                    zone_zero_milli: (length as i64 - in_zones).max(0),
+                    zone_one_milli: zone(self.zone_one),
+                    zone_two_milli: zone(self.zone_two),
+                    zone_three_milli: zone(self.zone_three),
+                    zone_four_milli: zone(self.zone_four),
+                    zone_five_milli: zone(self.zone_five),
+                },
+                distance_meter: self.distance,
+                altitude_gain_meter: self.altitude_gain,
+                altitude_change_meter: self.altitude_change,
+            }),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct JournalRow {
+    #[serde(rename = "Cycle start time")]
+    cycle_start: String,
+    #[serde(rename = "Cycle timezone")]
+    timezone: Option<String>,
+    #[serde(rename = "Question text")]
+    question: String,
+    #[serde(rename = "Answered yes")]
+    answered_yes: bool,
+    #[serde(rename = "Notes", default)]
+    notes: Option<String>,
+}
+
+impl JournalRow {
+    fn entry(&self) -> Result<JournalEntry> {
+        let offset = parse_offset(self.timezone.as_deref())?;
+        Ok(JournalEntry {
+            cycle_start: parse_time(&self.cycle_start, offset)?,
+            timezone_offset: offset_string(offset),
+            question: self.question.trim().to_string(),
+            answered_yes: self.answered_yes,
+            notes: self.notes.clone().filter(|n| !n.trim().is_empty()),
+        })
+    }
+}
@@ -0,0 +1,14 @@
+//! WHOOP API client, authentication and local storage behind the `whoopterm`
+//! dashboard. The TUI itself lives in `main.rs`.
+
+pub mod api;
+pub mod auth;
+pub mod backfill;
+pub mod config;
+pub mod data;
+pub mod demo;
+pub mod format;
+pub mod import;
+pub mod source;
+pub mod store;
+pub mod sync;
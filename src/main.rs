@@ -5,7 +5,7 @@ use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, Wrap},
+    widgets::{Block, BorderType, Borders, Cell, Clear, Paragraph, Row, Table, TableState, Wrap},
     Frame, Terminal,
 };
 use crossterm::{
@@ -14,19 +14,27 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-mod api;
-mod auth;
-mod config;
-mod data;
-
-use api::WhoopAPI;
-use data::{DashboardData, SleepScore};
+use whoopterm::api::{FetchProgress, RateLimit, WhoopAPI};
+use whoopterm::auth::reauth_cause;
+use whoopterm::backfill;
+use whoopterm::config::Config;
+use whoopterm::data::{BodyMeasurement, DashboardData, HistoryWindow, Section, SleepScore, Workout};
+use whoopterm::demo::DemoSource;
+use whoopterm::import;
+use whoopterm::source::{CacheSource, DataSource, FixtureSource};
+use whoopterm::store::{Retention, Store};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const REFRESH_INTERVAL: Duration = Duration::from_secs(300); // Auto-refresh every 5 minutes
 
+/// Builds the data source for a profile, so the TUI can switch profiles
+type SourceFactory = Box<dyn Fn(&str) -> Result<Box<dyn DataSource>>>;
+
 #[derive(Parser)]
 #[command(name = "whoopterm")]
 #[command(about = "WHOOP fitness dashboard for the terminal")]
@@ -39,42 +47,282 @@ struct Cli {
     /// Authenticate with WHOOP
     #[arg(short, long)]
     auth: bool,
+
+    /// With --auth: print the login URL and read the redirect back from stdin instead of opening a browser
+    #[arg(long, requires = "auth")]
+    no_browser: bool,
     
     /// Test API connectivity
     #[arg(long)]
     test: bool,
+
+    /// Days of history to show (default: 7, or history_days in the config)
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "since")]
+    days: Option<u32>,
+
+    /// Show history starting on this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date)]
+    since: Option<chrono::NaiveDate>,
+
+    /// Show history up to and including this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date, requires = "since")]
+    until: Option<chrono::NaiveDate>,
+
+    /// Where dashboard data comes from
+    #[arg(long, value_enum, default_value_t = SourceKind::Live)]
+    source: SourceKind,
+
+    /// Account profile to use, each with its own login and data (default: profile in the config)
+    #[arg(long, value_name = "NAME", global = true)]
+    profile: Option<String>,
+
+    /// OAuth redirect URI registered for your WHOOP app (default: http://localhost:8080/callback)
+    #[arg(long, value_name = "URL")]
+    redirect_uri: Option<String>,
+
+    /// Address the --auth callback listener binds to (default: 127.0.0.1)
+    #[arg(long, value_name = "ADDR")]
+    callback_address: Option<String>,
+
+    /// Port the --auth callback listener binds to (default: the redirect URI's)
+    #[arg(long, value_name = "PORT")]
+    callback_port: Option<u16>,
+
+    /// Seconds --auth waits for the browser login before giving up (default: 300)
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    login_timeout: Option<u64>,
+
+    /// Show local data only, without any network access (same as --source cache)
+    #[arg(long, conflicts_with = "source")]
+    offline: bool,
+
+    /// Directory of recorded API responses for --source fixtures
+    #[arg(long, value_name = "DIR", required_if_eq("source", "fixtures"))]
+    fixtures: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Sync recent history into the local store without opening the dashboard
+    Sync {
+        /// Walk the account's entire history instead, resuming an interrupted backfill
+        #[arg(long)]
+        backfill: bool,
+    },
+    /// Import a WHOOP data export (the zip or its unzipped folder) into the local store
+    Import {
+        #[arg(value_name = "DIR_OR_ZIP")]
+        path: PathBuf,
+    },
+    /// Inspect the configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// List account profiles
+    Profiles,
+    /// Inspect and maintain the local history store
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(clap::Subcommand)]
+enum DbCommand {
+    /// Show record counts, date spans and the store's size
+    Stats,
+    /// Delete history older than the given age
+    Prune {
+        /// How much to keep, e.g. 90d, 12w, 6m or 2y
+        #[arg(long, value_name = "AGE")]
+        keep: Retention,
+    },
+    /// Check for corrupt records and recoveries whose sleep is missing
+    Verify,
+}
+
+#[derive(clap::Subcommand)]
+enum ConfigCommand {
+    /// Print every setting's effective value and where it came from
+    Show,
+}
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+enum SourceKind {
+    /// The WHOOP API, backed by the local cache
+    Live,
+    /// Only the local cache, no network access
+    Cache,
+    /// Recorded API responses from --fixtures DIR
+    Fixtures,
+    /// Generated sample data
+    Demo,
+}
+
+impl Cli {
+    /// History window requested on the command line, if any
+    fn history_window(&self) -> Option<HistoryWindow> {
+        if let Some(since) = self.since {
+            Some(HistoryWindow::from_dates(since, self.until))
+        } else {
+            self.days.map(HistoryWindow::Days)
+        }
+    }
+
+    /// Settings given as flags, which override every other configuration layer
+    fn config_overrides(&self) -> Config {
+        Config {
+            history_days: self.days,
+            profile: self.profile.clone(),
+            redirect_uri: self.redirect_uri.clone(),
+            callback_address: self.callback_address.clone(),
+            callback_port: self.callback_port,
+            login_timeout: self.login_timeout,
+            ..Default::default()
+        }
+    }
+}
+
+fn parse_date(value: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|e| format!("expected YYYY-MM-DD: {}", e))
+}
+
+/// What fills the screen between header and footer
+#[derive(Clone, Copy, PartialEq)]
+enum View {
+    Dashboard,
+    Journal,
+}
+
+/// Overlay drawn on top of the dashboard
+#[derive(Clone, Copy, PartialEq)]
+enum Popup {
+    Profile,
+    WorkoutDetail,
 }
 
 struct App {
     data: Option<DashboardData>,
-    api: WhoopAPI,
+    source: Box<dyn DataSource>,
+    window: HistoryWindow,
     error_message: Option<String>,
     last_refresh: Option<Instant>,
     loading: bool,
+    /// The stored tokens were rejected and couldn't be refreshed
+    reauth_required: bool,
+    selected_workout: usize,
+    popup: Option<Popup>,
+    view: View,
+    selected_day: usize,
+    profile: String,
+    /// Profiles the user can switch between; empty when the source ignores profiles
+    profiles: Vec<String>,
+    source_factory: Option<SourceFactory>,
 }
 
 impl App {
-    fn new() -> Self {
+    fn new(source: Box<dyn DataSource>, window: HistoryWindow, profile: String) -> Self {
         Self {
             data: None,
-            api: WhoopAPI::new(),
+            source,
+            window,
             error_message: None,
             last_refresh: None,
             loading: false,
+            reauth_required: false,
+            selected_workout: 0,
+            popup: None,
+            view: View::Dashboard,
+            selected_day: 0,
+            profile,
+            profiles: Vec::new(),
+            source_factory: None,
+        }
+    }
+
+    fn can_switch_profile(&self) -> bool {
+        self.source_factory.is_some() && self.profiles.len() > 1
+    }
+
+    /// Switch to the next profile and load its data.
+    async fn switch_profile(&mut self) {
+        let Some(factory) = self.source_factory.as_ref().filter(|_| self.profiles.len() > 1) else {
+            return;
+        };
+        let next = self.profiles.iter().position(|p| *p == self.profile).map_or(0, |i| (i + 1) % self.profiles.len());
+        let profile = self.profiles[next].clone();
+        match factory(&profile) {
+            Ok(source) => {
+                self.source = source;
+                self.profile = profile;
+                self.data = None;
+                self.last_refresh = None;
+                self.reauth_required = false;
+                self.selected_workout = 0;
+                self.selected_day = 0;
+                self.popup = None;
+                let _ = self.load_data().await;
+            }
+            Err(e) => self.set_error(e),
         }
     }
 
+    fn workouts(&self) -> &[Workout] {
+        self.data.as_ref().map(|d| d.workouts.as_slice()).unwrap_or_default()
+    }
+
+    fn selected_workout(&self) -> Option<&Workout> {
+        self.workouts().get(self.selected_workout)
+    }
+
+    fn select_next_workout(&mut self) {
+        let count = self.workouts().len();
+        if count > 0 {
+            self.selected_workout = (self.selected_workout + 1).min(count - 1);
+        }
+    }
+
+    fn select_previous_workout(&mut self) {
+        self.selected_workout = self.selected_workout.saturating_sub(1);
+    }
+
+    fn day_count(&self) -> usize {
+        self.data.as_ref().map(|d| d.days().len()).unwrap_or_default()
+    }
+
+    /// Move the selection in whichever list the current view shows.
+    fn select_next(&mut self) {
+        match self.view {
+            View::Dashboard => self.select_next_workout(),
+            View::Journal => self.selected_day = (self.selected_day + 1).min(self.day_count().saturating_sub(1)),
+        }
+    }
+
+    fn select_previous(&mut self) {
+        match self.view {
+            View::Dashboard => self.select_previous_workout(),
+            View::Journal => self.selected_day = self.selected_day.saturating_sub(1),
+        }
+    }
+
+    fn toggle_view(&mut self) {
+        self.popup = None;
+        self.view = match self.view {
+            View::Dashboard => View::Journal,
+            View::Journal => View::Dashboard,
+        };
+    }
+
+    fn toggle_popup(&mut self, popup: Popup) {
+        self.popup = if self.popup == Some(popup) { None } else { Some(popup) };
+    }
+
     async fn load_data(&mut self) -> Result<()> {
         self.loading = true;
-        match self.api.load_cached_or_refresh().await {
-            Ok(data) => {
-                self.data = Some(data);
-                self.error_message = None;
-                self.last_refresh = Some(Instant::now());
-            }
-            Err(e) => {
-                self.error_message = Some(format!("{}", e));
-            }
+        match self.source.load(self.window).await {
+            Ok(data) => self.set_data(data),
+            Err(e) => self.set_error(e),
         }
         self.loading = false;
         Ok(())
@@ -82,21 +330,36 @@ impl App {
 
     async fn refresh_data(&mut self) -> Result<()> {
         self.loading = true;
-        match self.api.refresh_all_data().await {
-            Ok(data) => {
-                self.data = Some(data);
-                self.error_message = None;
-                self.last_refresh = Some(Instant::now());
-            }
-            Err(e) => {
-                self.error_message = Some(format!("{}", e));
-            }
+        match self.source.refresh(self.window).await {
+            Ok(data) => self.set_data(data),
+            Err(e) => self.set_error(e),
         }
         self.loading = false;
         Ok(())
     }
 
+    fn set_data(&mut self, data: DashboardData) {
+        self.data = Some(data);
+        self.error_message = None;
+        self.reauth_required = false;
+        self.last_refresh = Some(Instant::now());
+    }
+
+    fn set_error(&mut self, error: anyhow::Error) {
+        // Keep showing what we have; the header tells the user to log in again
+        if reauth_cause(&error).is_some() {
+            self.reauth_required = true;
+            if self.data.is_some() {
+                return;
+            }
+        }
+        self.error_message = Some(format!("{}", error));
+    }
+
     fn should_auto_refresh(&self) -> bool {
+        if self.reauth_required {
+            return false;
+        }
         if let Some(last) = self.last_refresh {
             last.elapsed() > REFRESH_INTERVAL
         } else {
@@ -109,11 +372,35 @@ impl App {
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let mut app = App::new();
+    let overrides = cli.config_overrides();
+    let config = match Config::load(&overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{:#}", e);
+            std::process::exit(2);
+        }
+    };
+    for warning in config.permission_warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    let window = match cli.history_window() {
+        Some(HistoryWindow::Range { since, until: Some(until) }) if until <= since => {
+            eprintln!("--until must not be before --since");
+            std::process::exit(2);
+        }
+        Some(window) => window,
+        None => config.history_days.map(HistoryWindow::Days).unwrap_or_default(),
+    };
 
     // Handle --auth and --test before entering TUI mode
     if cli.auth {
-        if let Err(e) = app.api.authenticate().await {
+        let api = WhoopAPI::with_config(config);
+        let result = if cli.no_browser {
+            api.authenticate_without_browser().await
+        } else {
+            api.authenticate().await
+        };
+        if let Err(e) = result {
             eprintln!("Authentication failed: {:#}", e);
             std::process::exit(1);
         }
@@ -121,9 +408,13 @@ async fn main() -> Result<()> {
     }
 
     if cli.test {
-        match app.api.test_connection().await {
+        let api = WhoopAPI::with_config(config);
+        match api.test_connection().await {
             Ok(_) => {
                 println!("API test successful!");
+                if let Some(rate_limit) = api.rate_limit() {
+                    println!("Rate limit: {}", format_rate_limit(&rate_limit));
+                }
             }
             Err(e) => {
                 eprintln!("API test failed: {}", e);
@@ -133,12 +424,65 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    match &cli.command {
+        Some(Command::Sync { backfill }) => {
+            if let Err(e) = sync_command(config, window, *backfill).await {
+                eprintln!("Sync failed: {:#}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Config(ConfigCommand::Show)) => {
+            config_show(&config);
+            return Ok(());
+        }
+        Some(Command::Profiles) => {
+            if let Err(e) = profiles_command(&config) {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Db(command)) => {
+            if let Err(e) = db_command(&config, command) {
+                eprintln!("{:#}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Import { path }) => {
+            if let Err(e) = import_command(&config, path) {
+                eprintln!("Import failed: {:#}", e);
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        None => {}
+    }
+
+    // Report progress of long multi-page fetches, but only until the TUI takes over the screen
+    let show_progress = Arc::new(AtomicBool::new(true));
+    let profile = config.profile().to_string();
+    let profiles = config.profile_names().unwrap_or_default();
+    let kind = if cli.offline { SourceKind::Cache } else { cli.source };
+    let fixtures = cli.fixtures.clone();
+    let mut app = App::new(build_source(kind, &fixtures, config, &show_progress), window, profile);
+    if matches!(kind, SourceKind::Live | SourceKind::Cache) {
+        let show = show_progress.clone();
+        app.profiles = profiles;
+        app.source_factory = Some(Box::new(move |profile: &str| {
+            let config = Config::load(&Config { profile: Some(profile.to_string()), ..overrides.clone() })?;
+            Ok(build_source(kind, &fixtures, config, &show))
+        }));
+    }
+
     // Load data before entering TUI
     if cli.refresh {
         let _ = app.refresh_data().await;
     } else {
         let _ = app.load_data().await;
     }
+    show_progress.store(false, Ordering::Relaxed);
 
     // Setup terminal
     enable_raw_mode()?;
@@ -166,6 +510,151 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+fn build_source(kind: SourceKind, fixtures: &Option<PathBuf>, config: Config, show_progress: &Arc<AtomicBool>) -> Box<dyn DataSource> {
+    match kind {
+        SourceKind::Live => {
+            let mut api = WhoopAPI::with_config(config);
+            let show = show_progress.clone();
+            api.set_progress(Some(Box::new(move |p: &FetchProgress| {
+                if show.load(Ordering::Relaxed) && p.page > 1 {
+                    eprintln!("Fetching {}: page {} ({} records)", p.endpoint, p.page, p.records);
+                }
+            })));
+            Box::new(api)
+        }
+        SourceKind::Cache => Box::new(CacheSource::new(config)),
+        SourceKind::Fixtures => Box::new(FixtureSource::new(fixtures.clone().expect("--fixtures is required by clap"))),
+        SourceKind::Demo => Box::new(DemoSource),
+    }
+}
+
+async fn sync_command(config: Config, window: HistoryWindow, backfill: bool) -> Result<()> {
+    let mut api = WhoopAPI::with_config(config.clone());
+    if !backfill {
+        api.set_progress(Some(Box::new(|p: &FetchProgress| {
+            eprintln!("Fetching {}: page {} ({} records)", p.endpoint, p.page, p.records);
+        })));
+        let data = api.refresh_all_data(window).await?;
+        println!("Synced {}:", window.label());
+        println!("  {:<10} {:>6}", Section::Recovery.name(), data.recovery.len());
+        println!("  {:<10} {:>6}", Section::Sleep.name(), data.sleep.len());
+        println!("  {:<10} {:>6}", Section::Workouts.name(), data.workouts.len());
+        println!("  {:<10} {:>6}", Section::Cycles.name(), data.cycles.len());
+        for (section, error) in &data.errors {
+            eprintln!("  {} failed: {}", section.name(), error);
+        }
+        if !data.errors.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut store = Store::open(&config)?;
+    let resumed = store.backfill_checkpoint()?.collections;
+    if !resumed.is_empty() {
+        println!("Resuming backfill ({} of 4 record types started)", resumed.len());
+    }
+
+    let checkpoint = backfill::backfill(&api, &mut store, |section, progress| {
+        eprintln!(
+            "Backfilling {}: {} records, back to {}",
+            section.name(),
+            progress.fetched,
+            progress.until.with_timezone(&chrono::Local).format("%Y-%m-%d"),
+        );
+    }).await?;
+
+    println!("Backfill complete:");
+    for (section, progress) in &checkpoint.collections {
+        println!("  {:<10} {:>6} records ({} new or updated)", section.name(), progress.fetched, progress.written);
+    }
+    if let Some(retention) = config.retention {
+        store.prune(retention.cutoff(chrono::Utc::now()))?;
+        println!("Kept the last {} of history (retention setting)", retention);
+    }
+    Ok(())
+}
+
+fn config_show(config: &Config) {
+    match whoopterm::config::config_file() {
+        Some(path) if path.exists() => println!("Config file: {}", path.display()),
+        Some(path) => println!("Config file: {} (not present)", path.display()),
+        None => println!("Config file: none (no config directory on this platform)"),
+    }
+    println!();
+
+    let settings = config.settings();
+    let width = settings.iter().map(|(key, _, _)| key.len()).max().unwrap_or_default();
+    for (key, value, origin) in settings {
+        match value {
+            None => println!("{:<width$}  (not set)", key, width = width),
+            Some(_) if key == "client_secret" => println!("{:<width$}  ********  ({})", key, origin, width = width),
+            Some(value) => println!("{:<width$}  {}  ({})", key, value, origin, width = width),
+        }
+    }
+}
+
+fn profiles_command(config: &Config) -> Result<()> {
+    for name in config.profile_names()? {
+        let marker = if name == config.profile() { "*" } else { " " };
+        let logged_in = config.profile_dir(&name)?.join("tokens.json").exists();
+        println!("{} {:<20} {}", marker, name, if logged_in { "logged in" } else { "not logged in" });
+    }
+    Ok(())
+}
+
+fn db_command(config: &Config, command: &DbCommand) -> Result<()> {
+    let mut store = Store::open(config)?;
+    match command {
+        DbCommand::Stats => {
+            let date = |at: Option<chrono::DateTime<chrono::Utc>>| {
+                at.map(|at| at.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string()).unwrap_or_else(|| "-".to_string())
+            };
+            println!("{} ({:.1} MB)", config.data_dir()?.join("history.db").display(), store.size()? as f64 / 1_000_000.0);
+            for table in store.stats()? {
+                println!("  {:<10} {:>7}  {} to {}", table.table, table.records, date(table.first), date(table.last));
+            }
+            match config.retention {
+                Some(retention) => println!("Retention: {} (pruned after every sync)", retention),
+                None => println!("Retention: keep everything"),
+            }
+        }
+        DbCommand::Prune { keep } => {
+            let cutoff = keep.cutoff(chrono::Utc::now());
+            let deleted = store.prune(cutoff)?;
+            println!("Pruned history before {}:", cutoff.with_timezone(&chrono::Local).format("%Y-%m-%d"));
+            for (table, count) in deleted {
+                println!("  {:<10} {:>7} deleted", table, count);
+            }
+        }
+        DbCommand::Verify => {
+            let issues = store.verify()?;
+            for issue in &issues {
+                println!("{} {}: {}", issue.table, issue.id, issue.problem);
+            }
+            if !issues.is_empty() {
+                eprintln!("{} problem(s) found", issues.len());
+                std::process::exit(1);
+            }
+            println!("No problems found");
+        }
+    }
+    Ok(())
+}
+
+fn import_command(config: &Config, path: &std::path::Path) -> Result<()> {
+    // The export doesn't say whose it is; use the synced profile when there is one
+    let user_id = config.load_cache().ok().and_then(|c| c.profile).map(|p| p.user_id).unwrap_or_default();
+    let mut store = Store::open(config)?;
+    let report = import::import(path, &mut store, user_id)?;
+
+    println!("Imported {}:", path.display());
+    for (kind, count) in &report.counts {
+        println!("  {:<10} {:>6} new ({} already stored)", kind, count.imported, count.duplicates);
+    }
+    Ok(())
+}
+
 async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
     let mut last_tick = Instant::now();
     let tick_rate = Duration::from_millis(250);
@@ -185,10 +674,20 @@ async fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Resul
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
                 match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Esc if app.popup.is_some() => app.popup = None,
+                    KeyCode::Esc => return Ok(()),
                     KeyCode::Char('r') => {
                         let _ = app.refresh_data().await;
                     }
+                    KeyCode::Tab => app.toggle_view(),
+                    KeyCode::Char('p') => app.toggle_popup(Popup::Profile),
+                    KeyCode::Char('P') => app.switch_profile().await,
+                    KeyCode::Enter if app.view == View::Dashboard && app.selected_workout().is_some() => {
+                        app.toggle_popup(Popup::WorkoutDetail)
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => app.select_next(),
+                    KeyCode::Up | KeyCode::Char('k') => app.select_previous(),
                     _ => {}
                 }
             }
@@ -213,7 +712,7 @@ fn ui(f: &mut Frame, app: &App) {
         .margin(1)
         .constraints([
             Constraint::Length(1),  // Header bar
-            Constraint::Length(10), // Recovery + Sleep row
+            Constraint::Length(10), // Recovery + Strain + Sleep row
             Constraint::Min(6),     // Sleep history (flexible)
             Constraint::Min(6),     // Workouts (flexible)
             Constraint::Length(1),  // Footer
@@ -229,15 +728,31 @@ fn ui(f: &mut Frame, app: &App) {
         return;
     }
 
-    if let Some(data) = &app.data {
-        // Recovery + Sleep side by side
+    if let (Some(data), View::Journal) = (&app.data, app.view) {
+        let area = Rect { height: chunks[4].y - chunks[1].y, ..chunks[1] };
+        render_journal(f, area, data, app.selected_day);
+        if app.popup == Some(Popup::Profile) {
+            render_profile_popup(f, size, data);
+        }
+    } else if let Some(data) = &app.data {
+        // Recovery, Strain + Sleep side by side
         render_recovery_and_sleep(f, chunks[1], data);
         
         // Sleep history
         render_sleep_history(f, chunks[2], data);
         
         // Workouts
-        render_workouts(f, chunks[3], data);
+        render_workouts(f, chunks[3], data, app.selected_workout);
+
+        match app.popup {
+            Some(Popup::Profile) => render_profile_popup(f, size, data),
+            Some(Popup::WorkoutDetail) => {
+                if let Some(workout) = app.selected_workout() {
+                    render_workout_detail_popup(f, size, workout, data.body.as_ref());
+                }
+            }
+            None => {}
+        }
     } else if app.loading {
         let loading = Paragraph::new("Loading...")
             .style(Style::default().fg(Color::Cyan))
@@ -246,7 +761,7 @@ fn ui(f: &mut Frame, app: &App) {
     }
 
     // Footer
-    render_footer(f, chunks[4]);
+    render_footer(f, chunks[4], app);
 }
 
 fn render_header(f: &mut Frame, area: Rect, app: &App) {
@@ -255,42 +770,94 @@ fn render_header(f: &mut Frame, area: Rect, app: &App) {
         .map(|p| format!("{} {}", p.first_name, p.last_name))
         .unwrap_or_else(|| "WHOOPTERM".to_string());
     
-    let refresh_text = if let Some(last) = app.last_refresh {
-        let elapsed = last.elapsed();
-        if elapsed.as_secs() < 60 {
-            "just now".to_string()
-        } else if elapsed.as_secs() < 3600 {
-            format!("{}m ago", elapsed.as_secs() / 60)
-        } else {
-            format!("{}h ago", elapsed.as_secs() / 3600)
-        }
-    } else {
-        "never".to_string()
-    };
+    let freshness = app.data.as_ref().map(format_staleness).unwrap_or_else(|| "Never updated".to_string());
+    let offline = app.data.as_ref().and_then(|d| d.offline.as_deref());
 
-    let header_spans = vec![
+    let mut header_spans = vec![
         Span::styled(profile_name, Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
         Span::styled("  |  ", Style::default().fg(Color::DarkGray)),
-        Span::styled(format!("Last updated: {}", refresh_text), Style::default().fg(Color::Gray)),
-        Span::styled("  |  ", Style::default().fg(Color::DarkGray)),
-        Span::styled(format!("v{}", VERSION), Style::default().fg(Color::DarkGray)),
     ];
+    if let Some(reason) = offline {
+        let reason: String = reason.lines().next().unwrap_or_default().chars().take(60).collect();
+        header_spans.push(Span::styled("OFFLINE", Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
+        header_spans.push(Span::styled(format!(" ({})  ", reason), Style::default().fg(Color::Yellow)));
+    }
+    let freshness_color = if offline.is_some() { Color::Yellow } else { Color::Gray };
+    header_spans.push(Span::styled(freshness, Style::default().fg(freshness_color)));
+    if app.reauth_required {
+        header_spans.push(Span::styled("  |  ", Style::default().fg(Color::DarkGray)));
+        header_spans.push(Span::styled(
+            "Re-authentication required: run whoopterm --auth",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if app.profile != whoopterm::config::DEFAULT_PROFILE || app.can_switch_profile() {
+        header_spans.push(Span::styled("  |  ", Style::default().fg(Color::DarkGray)));
+        header_spans.push(Span::styled(format!("Account: {}", app.profile), Style::default().fg(Color::Gray)));
+    }
+    if app.source.name() != "live" {
+        header_spans.push(Span::styled("  |  ", Style::default().fg(Color::DarkGray)));
+        header_spans.push(Span::styled(format!("[{}]", app.source.name()), Style::default().fg(Color::Yellow)));
+    }
+    if let Some(rate_limit) = app.source.rate_limit() {
+        header_spans.push(Span::styled("  |  ", Style::default().fg(Color::DarkGray)));
+        header_spans.push(Span::styled(format!("API {}", format_rate_limit(&rate_limit)), Style::default().fg(Color::Gray)));
+    }
+    header_spans.push(Span::styled("  |  ", Style::default().fg(Color::DarkGray)));
+    header_spans.push(Span::styled(format!("v{}", VERSION), Style::default().fg(Color::DarkGray)));
 
     let header = Paragraph::new(Line::from(header_spans));
     f.render_widget(header, area);
 }
 
+fn format_age(age: chrono::Duration) -> String {
+    if age.num_minutes() < 1 {
+        "just now".to_string()
+    } else if age.num_hours() < 1 {
+        format!("{}m ago", age.num_minutes())
+    } else if age.num_days() < 1 {
+        format!("{}h ago", age.num_hours())
+    } else {
+        format!("{}d ago", age.num_days())
+    }
+}
+
+/// How long ago each section was fetched: "Updated 5m ago" when they agree,
+/// otherwise e.g. "Updated: recovery, sleep 5m ago · profile 2d ago".
+fn format_staleness(data: &DashboardData) -> String {
+    let now = chrono::Utc::now();
+    let mut groups: Vec<(String, Vec<&str>)> = Vec::new();
+    for section in Section::ALL {
+        let age = data.fetched_at.get(&section).map_or_else(|| "never".to_string(), |at| format_age(now - *at));
+        match groups.iter_mut().find(|(group, _)| *group == age) {
+            Some((_, sections)) => sections.push(section.name()),
+            None => groups.push((age, vec![section.name()])),
+        }
+    }
+
+    match groups.as_slice() {
+        [(age, _)] if age == "never" => "Never updated".to_string(),
+        [(age, _)] => format!("Updated {}", age),
+        _ => {
+            let parts: Vec<String> = groups.iter().map(|(age, sections)| format!("{} {}", sections.join(", "), age)).collect();
+            format!("Updated: {}", parts.join(" · "))
+        }
+    }
+}
+
 fn render_recovery_and_sleep(f: &mut Frame, area: Rect, data: &DashboardData) {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(35), // Recovery
-            Constraint::Percentage(65), // Sleep
+            Constraint::Percentage(26), // Recovery
+            Constraint::Percentage(22), // Strain
+            Constraint::Percentage(52), // Sleep
         ])
         .split(area);
 
     render_recovery_panel(f, chunks[0], data);
-    render_sleep_panel(f, chunks[1], data);
+    render_strain_panel(f, chunks[1], data);
+    render_sleep_panel(f, chunks[2], data);
 }
 
 fn render_recovery_panel(f: &mut Frame, area: Rect, data: &DashboardData) {
@@ -304,6 +871,11 @@ fn render_recovery_panel(f: &mut Frame, area: Rect, data: &DashboardData) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
+    if let Some(error) = data.errors.get(&Section::Recovery) {
+        render_section_error(f, inner, error);
+        return;
+    }
+
     if let Some(recovery) = data.recovery.first().and_then(|r| r.score.as_ref()) {
         let score = recovery.recovery_score as i32;
         let color = get_recovery_color(score);
@@ -352,6 +924,76 @@ fn render_recovery_panel(f: &mut Frame, area: Rect, data: &DashboardData) {
     }
 }
 
+fn render_strain_panel(f: &mut Frame, area: Rect, data: &DashboardData) {
+    let block = Block::default()
+        .title(" Strain ")
+        .title_style(Style::default().fg(Color::Cyan))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::DarkGray));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if let Some(error) = data.errors.get(&Section::Cycles) {
+        render_section_error(f, inner, error);
+        return;
+    }
+
+    // Cycles come back newest first, so the first one is today's (possibly still open) cycle
+    let Some(cycle) = data.cycles.first() else {
+        let no_data = Paragraph::new("No strain data")
+            .style(Style::default().fg(Color::Gray))
+            .alignment(Alignment::Center);
+        f.render_widget(no_data, inner);
+        return;
+    };
+
+    let status = match cycle.end {
+        Some(end) => format!("Ended {}", format_date(&end)),
+        None => format!("In progress since {}", cycle.start.with_timezone(&chrono::Local).format("%H:%M")),
+    };
+
+    let Some(score) = &cycle.score else {
+        let text = vec![
+            Line::from(Span::styled("Strain not scored", Style::default().fg(Color::Gray))),
+            Line::from(""),
+            Line::from(Span::styled(status, Style::default().fg(Color::DarkGray))),
+        ];
+        f.render_widget(Paragraph::new(text).alignment(Alignment::Center), inner);
+        return;
+    };
+
+    let color = get_strain_color(score.strain);
+    let bar_width = (inner.width as usize).saturating_sub(7);
+    // Strain is scored on a 0-21 scale
+    let bar = create_horizontal_bar((score.strain * 10.0) as i32, 210, bar_width);
+
+    let text = vec![
+        Line::from(vec![
+            Span::styled(format!("{:4.1}", score.strain), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::styled(" ", Style::default()),
+            Span::styled(bar, Style::default().fg(color)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Energy ", Style::default().fg(Color::Gray)),
+            Span::styled(format!("{:.0} kJ", score.kilojoule), Style::default().fg(Color::White)),
+        ]),
+        Line::from(vec![
+            Span::styled("Avg HR ", Style::default().fg(Color::Gray)),
+            Span::styled(format!("{} bpm", score.average_heart_rate), Style::default().fg(Color::White)),
+        ]),
+        Line::from(vec![
+            Span::styled("Max HR ", Style::default().fg(Color::Gray)),
+            Span::styled(format!("{} bpm", score.max_heart_rate), Style::default().fg(Color::White)),
+        ]),
+        Line::from(Span::styled(status, Style::default().fg(Color::DarkGray))),
+    ];
+
+    f.render_widget(Paragraph::new(text), inner);
+}
+
 fn render_sleep_panel(f: &mut Frame, area: Rect, data: &DashboardData) {
     let block = Block::default()
         .title(" Last Night's Sleep ")
@@ -363,6 +1005,11 @@ fn render_sleep_panel(f: &mut Frame, area: Rect, data: &DashboardData) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
+    if let Some(error) = data.errors.get(&Section::Sleep) {
+        render_section_error(f, inner, error);
+        return;
+    }
+
     if let Some(sleep) = data.sleep.first() {
         if let Some(score) = &sleep.score {
             render_sleep_content(f, inner, score);
@@ -431,8 +1078,17 @@ fn create_stage_line<'a>(label: &'a str, mins: i64, total: i64, color: Color, wi
 }
 
 fn render_sleep_history(f: &mut Frame, area: Rect, data: &DashboardData) {
+    let nights: Vec<_> = data.sleep.iter().filter(|s| s.score.is_some()).collect();
+    // Rows left inside the borders and below the header; the newest nights
+    // are shown and the title says how many didn't fit
+    let capacity = area.height.saturating_sub(3) as usize;
+    let title = if nights.len() > capacity {
+        format!(" Sleep History ({}, showing {} of {}) ", data.window.label(), capacity, nights.len())
+    } else {
+        format!(" Sleep History ({}) ", data.window.label())
+    };
     let block = Block::default()
-        .title(" Sleep History (7d) ")
+        .title(title)
         .title_style(Style::default().fg(Color::Cyan))
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
@@ -441,6 +1097,11 @@ fn render_sleep_history(f: &mut Frame, area: Rect, data: &DashboardData) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
+    if let Some(error) = data.errors.get(&Section::Sleep) {
+        render_section_error(f, inner, error);
+        return;
+    }
+
     let header_cells = vec![
         Cell::from("Date").style(Style::default().fg(Color::Gray)),
         Cell::from("Hours").style(Style::default().fg(Color::Gray)),
@@ -449,11 +1110,9 @@ fn render_sleep_history(f: &mut Frame, area: Rect, data: &DashboardData) {
     ];
     let header = Row::new(header_cells).height(1);
 
-    let rows: Vec<Row> = data
-        .sleep
+    let rows: Vec<Row> = nights
         .iter()
-        .take(7)
-        .filter(|s| s.score.is_some())
+        .take(capacity)
         .map(|sleep| {
             let date = format_date(&sleep.start);
             let hours = sleep.score.as_ref().map(|s| s.stage_summary.total_in_bed_time_milli as f64 / 3600000.0).unwrap_or(0.0);
@@ -485,9 +1144,9 @@ fn render_sleep_history(f: &mut Frame, area: Rect, data: &DashboardData) {
     f.render_widget(table, inner);
 }
 
-fn render_workouts(f: &mut Frame, area: Rect, data: &DashboardData) {
+fn render_workouts(f: &mut Frame, area: Rect, data: &DashboardData, selected: usize) {
     let block = Block::default()
-        .title(" Recent Workouts ")
+        .title(format!(" Workouts ({}) ", data.window.label()))
         .title_style(Style::default().fg(Color::Cyan))
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
@@ -496,19 +1155,24 @@ fn render_workouts(f: &mut Frame, area: Rect, data: &DashboardData) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
+    if let Some(error) = data.errors.get(&Section::Workouts) {
+        render_section_error(f, inner, error);
+        return;
+    }
+
     let header_cells = vec![
         Cell::from("Date").style(Style::default().fg(Color::Gray)),
         Cell::from("Activity").style(Style::default().fg(Color::Gray)),
         Cell::from("Strain").style(Style::default().fg(Color::Gray)),
         Cell::from("Duration").style(Style::default().fg(Color::Gray)),
         Cell::from("Avg HR").style(Style::default().fg(Color::Gray)),
+        Cell::from("Max HR").style(Style::default().fg(Color::Gray)),
     ];
     let header = Row::new(header_cells).height(1);
 
     let rows: Vec<Row> = data
         .workouts
         .iter()
-        .take(5)
         .filter(|w| w.score.is_some())
         .map(|workout| {
             let date = format_date(&workout.start);
@@ -516,18 +1180,18 @@ fn render_workouts(f: &mut Frame, area: Rect, data: &DashboardData) {
             let score = workout.score.as_ref().unwrap();
             let strain = score.strain;
             let duration_mins = (workout.end.timestamp() - workout.start.timestamp()) / 60;
-            let avg_hr = score.average_heart_rate;
 
             let strain_bar_width = 8;
             let strain_bar = create_horizontal_bar((strain * 5.0) as i32, 100, strain_bar_width);
-            let strain_color = if strain >= 15.0 { Color::Red } else if strain >= 10.0 { Color::Yellow } else { Color::Green };
+            let strain_color = get_strain_color(strain);
 
             let cells = vec![
                 Cell::from(date).style(Style::default().fg(Color::White)),
                 Cell::from(activity.clone()).style(Style::default().fg(Color::White)),
                 Cell::from(format!("{} {:.1}", strain_bar, strain)).style(Style::default().fg(strain_color)),
                 Cell::from(format_duration(duration_mins)).style(Style::default().fg(Color::Gray)),
-                Cell::from(format!("{}", avg_hr)).style(Style::default().fg(Color::Gray)),
+                Cell::from(format_heart_rate(score.average_heart_rate, data.body.as_ref())).style(Style::default().fg(Color::Gray)),
+                Cell::from(format_heart_rate(score.max_heart_rate, data.body.as_ref())).style(Style::default().fg(Color::Gray)),
             ];
             Row::new(cells).height(1)
         })
@@ -537,21 +1201,227 @@ fn render_workouts(f: &mut Frame, area: Rect, data: &DashboardData) {
         Constraint::Length(10),
         Constraint::Length(16),
         Constraint::Length(14),
+        Constraint::Length(10),
+        Constraint::Length(9),
+        Constraint::Length(9),
+    ])
+    .header(header)
+    .column_spacing(2)
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    let mut state = TableState::default().with_selected(Some(selected));
+    f.render_stateful_widget(table, inner, &mut state);
+}
+
+fn render_journal(f: &mut Frame, area: Rect, data: &DashboardData, selected: usize) {
+    let block = Block::default()
+        .title(format!(" Journal ({}) ", data.window.label()))
+        .title_style(Style::default().fg(Color::Cyan))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::DarkGray));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if let Some(error) = data.errors.get(&Section::Journal) {
+        render_section_error(f, inner, error);
+        return;
+    }
+
+    let days = data.days();
+    if days.iter().all(|d| d.journal.is_empty()) {
+        let empty = Paragraph::new("No journal answers in this window. Import them with: whoopterm import <export>")
+            .style(Style::default().fg(Color::DarkGray))
+            .wrap(Wrap { trim: true });
+        f.render_widget(empty, inner);
+        return;
+    }
+
+    let header_cells = vec![
+        Cell::from("Date").style(Style::default().fg(Color::Gray)),
+        Cell::from("Recovery").style(Style::default().fg(Color::Gray)),
+        Cell::from("Sleep").style(Style::default().fg(Color::Gray)),
+        Cell::from("Journal").style(Style::default().fg(Color::Gray)),
+    ];
+    let header = Row::new(header_cells).height(1);
+
+    let rows: Vec<Row> = days
+        .iter()
+        .map(|day| {
+            let recovery = day.recovery.as_ref().and_then(|r| r.score.as_ref()).map(|s| s.recovery_score as i32);
+            let recovery_cell = match recovery {
+                Some(score) => Cell::from(format!("{}%", score)).style(Style::default().fg(get_recovery_color(score))),
+                None => Cell::from("–").style(Style::default().fg(Color::DarkGray)),
+            };
+            let hours = day.sleep.as_ref()
+                .and_then(|s| s.score.as_ref())
+                .map(|s| s.stage_summary.total_in_bed_time_milli as f64 / 3600000.0);
+            let sleep_cell = match hours {
+                Some(hours) => Cell::from(format!("{:.1}h", hours)).style(Style::default().fg(Color::White)),
+                None => Cell::from("–").style(Style::default().fg(Color::DarkGray)),
+            };
+
+            let answers: Vec<Line> = day.journal
+                .iter()
+                .map(|entry| {
+                    let (mark, color) = if entry.answered_yes { ("✓ ", Color::Green) } else { ("✗ ", Color::DarkGray) };
+                    let mut spans = vec![
+                        Span::styled(mark, Style::default().fg(color)),
+                        Span::styled(entry.question.clone(), Style::default().fg(Color::White)),
+                    ];
+                    if let Some(notes) = &entry.notes {
+                        spans.push(Span::styled(format!("  {}", notes), Style::default().fg(Color::Gray)));
+                    }
+                    Line::from(spans)
+                })
+                .collect();
+            let height = answers.len().max(1) as u16;
+
+            let cells = vec![
+                Cell::from(day.date.format("%a %b %d").to_string()).style(Style::default().fg(Color::White)),
+                recovery_cell,
+                sleep_cell,
+                Cell::from(answers),
+            ];
+            Row::new(cells).height(height).bottom_margin(1)
+        })
+        .collect();
+
+    let table = Table::new(rows, vec![
         Constraint::Length(10),
         Constraint::Length(8),
+        Constraint::Length(6),
+        Constraint::Min(20),
     ])
     .header(header)
-    .column_spacing(2);
+    .column_spacing(2)
+    .row_highlight_style(Style::default().add_modifier(Modifier::BOLD));
 
-    f.render_widget(table, inner);
+    let mut state = TableState::default().with_selected(Some(selected));
+    f.render_stateful_widget(table, inner, &mut state);
+}
+
+fn render_profile_popup(f: &mut Frame, area: Rect, data: &DashboardData) {
+    let popup_area = centered_rect(50, 40, area);
+
+    let mut text = Vec::new();
+    if let Some(error) = data.errors.get(&Section::Profile) {
+        text.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))));
+    }
+    if let Some(profile) = &data.profile {
+        text.push(detail_line("Name", format!("{} {}", profile.first_name, profile.last_name)));
+        text.push(detail_line("Email", profile.email.clone()));
+    }
+    match &data.body {
+        Some(body) => {
+            text.push(Line::from(""));
+            text.push(detail_line("Height", format!("{:.2} m", body.height_meter)));
+            text.push(detail_line("Weight", format!("{:.1} kg", body.weight_kilogram)));
+            text.push(detail_line("Max HR", format!("{} bpm", body.max_heart_rate)));
+        }
+        None => {
+            text.push(Line::from(""));
+            text.push(Line::from(Span::styled(
+                "No body measurements (re-run whoopterm --auth to grant access)",
+                Style::default().fg(Color::Gray),
+            )));
+            if let Some(error) = data.errors.get(&Section::Body) {
+                text.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red))));
+            }
+        }
+    }
+
+    let widget = Paragraph::new(text)
+        .wrap(Wrap { trim: true })
+        .block(popup_block(" Profile "));
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(widget, popup_area);
 }
 
-fn render_footer(f: &mut Frame, area: Rect) {
-    let footer = Paragraph::new("  r Refresh  q Quit")
-        .style(Style::default().fg(Color::DarkGray));
+fn render_workout_detail_popup(f: &mut Frame, area: Rect, workout: &Workout, body: Option<&BodyMeasurement>) {
+    let popup_area = centered_rect(60, 70, area);
+    let title = format!(" {} ", workout.sport_name);
+    let duration_mins = (workout.end.timestamp() - workout.start.timestamp()) / 60;
+
+    let mut text = vec![
+        detail_line("When", format!(
+            "{} {}-{}",
+            format_date(&workout.start),
+            workout.start.format("%H:%M"),
+            workout.end.format("%H:%M"),
+        )),
+        detail_line("Duration", format_duration(duration_mins)),
+    ];
+
+    if let Some(score) = &workout.score {
+        text.push(detail_line("Strain", format!("{:.1}", score.strain)));
+        text.push(detail_line("Energy", format!("{:.0} kJ", score.kilojoule)));
+        text.push(detail_line("Avg HR", format_heart_rate(score.average_heart_rate, body)));
+        text.push(detail_line("Max HR", format_heart_rate(score.max_heart_rate, body)));
+        if let Some(distance) = score.distance_meter {
+            text.push(detail_line("Distance", format!("{:.2} km", distance / 1000.0)));
+        }
+        if let Some(gain) = score.altitude_gain_meter {
+            text.push(detail_line("Elevation", format!("+{:.0} m", gain)));
+        }
+        text.push(detail_line("Recorded", format!("{:.0}%", score.percent_recorded)));
+
+        let zones = &score.zone_durations;
+        let zone_millis = [
+            zones.zone_zero_milli,
+            zones.zone_one_milli,
+            zones.zone_two_milli,
+            zones.zone_three_milli,
+            zones.zone_four_milli,
+            zones.zone_five_milli,
+        ];
+        let total: i64 = zone_millis.iter().sum();
+        let bar_width = (popup_area.width as usize).saturating_sub(24);
+
+        text.push(Line::from(""));
+        for (zone, millis) in zone_millis.iter().enumerate() {
+            let mins = millis / 60000;
+            text.push(Line::from(vec![
+                Span::styled(format!("Zone {}   ", zone), Style::default().fg(Color::Gray)),
+                Span::styled(format!("{:>6} ", format_duration(mins)), Style::default().fg(Color::White)),
+                Span::styled(create_proportional_bar(*millis, total, bar_width), Style::default().fg(Color::Cyan)),
+            ]));
+        }
+    } else {
+        text.push(Line::from(Span::styled("Workout not scored", Style::default().fg(Color::Gray))));
+    }
+
+    let widget = Paragraph::new(text).block(popup_block(&title));
+
+    f.render_widget(Clear, popup_area);
+    f.render_widget(widget, popup_area);
+}
+
+fn render_footer(f: &mut Frame, area: Rect, app: &App) {
+    let mut keys = match app.view {
+        View::Dashboard => "  r Refresh  ↑↓ Select  Enter Details  Tab Journal  p Profile",
+        View::Journal => "  r Refresh  ↑↓ Scroll  Tab Dashboard  p Profile",
+    }.to_string();
+    if app.can_switch_profile() {
+        keys.push_str("  P Switch account");
+    }
+    keys.push_str("  q Quit");
+    let footer = Paragraph::new(keys).style(Style::default().fg(Color::DarkGray));
     f.render_widget(footer, area);
 }
 
+/// Shown inside a panel whose section failed to load, so the rest of the dashboard still renders.
+fn render_section_error(f: &mut Frame, area: Rect, error: &str) {
+    let text = vec![
+        Line::from(Span::styled("Failed to load", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD))),
+        Line::from(Span::styled(error.to_string(), Style::default().fg(Color::Red))),
+    ];
+    let widget = Paragraph::new(text).wrap(Wrap { trim: true });
+    f.render_widget(widget, area);
+}
+
 fn render_error_popup(f: &mut Frame, area: Rect, error: &str) {
     let popup_area = centered_rect(80, 40, area);
     
@@ -577,11 +1447,47 @@ fn render_error_popup(f: &mut Frame, area: Rect, error: &str) {
 // Helpers
 // ─────────────────────────────────────────────────────────────────────────────
 
+fn popup_block(title: &str) -> Block<'_> {
+    Block::default()
+        .title(title)
+        .title_style(Style::default().fg(Color::Cyan))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan))
+}
+
+fn detail_line<'a>(label: &str, value: String) -> Line<'a> {
+    Line::from(vec![
+        Span::styled(format!("{:<10}", label), Style::default().fg(Color::Gray)),
+        Span::styled(value, Style::default().fg(Color::White)),
+    ])
+}
+
+/// Format a heart rate, adding its share of the user's max HR when known.
+fn format_heart_rate(bpm: i32, body: Option<&BodyMeasurement>) -> String {
+    match body.and_then(|b| b.percent_of_max_hr(bpm)) {
+        Some(percent) => format!("{} {:.0}%", bpm, percent),
+        None => format!("{}", bpm),
+    }
+}
+
+fn format_rate_limit(rate_limit: &RateLimit) -> String {
+    let budget = match (rate_limit.remaining, rate_limit.limit) {
+        (Some(remaining), Some(limit)) => format!("{}/{} requests left", remaining, limit),
+        (Some(remaining), None) => format!("{} requests left", remaining),
+        _ => "budget unknown".to_string(),
+    };
+    match rate_limit.reset {
+        Some(reset) => format!("{} (resets in {}s)", budget, reset.as_secs()),
+        None => budget,
+    }
+}
+
 fn create_horizontal_bar(value: i32, max: i32, width: usize) -> String {
     if width == 0 {
         return String::new();
     }
-    let ratio = (value as f64 / max as f64).min(1.0).max(0.0);
+    let ratio = (value as f64 / max as f64).clamp(0.0, 1.0);
     let filled = (ratio * width as f64) as usize;
     let filled_str = "█".repeat(filled);
     let empty_str = "░".repeat(width.saturating_sub(filled));
@@ -592,13 +1498,23 @@ fn create_proportional_bar(value: i64, total: i64, width: usize) -> String {
     if total == 0 || width == 0 {
         return "░".repeat(width);
     }
-    let ratio = (value as f64 / total as f64).min(1.0).max(0.0);
+    let ratio = (value as f64 / total as f64).clamp(0.0, 1.0);
     let filled = (ratio * width as f64) as usize;
     let filled_str = "█".repeat(filled);
     let empty_str = "░".repeat(width.saturating_sub(filled));
     format!("{}{}", filled_str, empty_str)
 }
 
+fn get_strain_color(strain: f64) -> Color {
+    if strain >= 15.0 {
+        Color::Red
+    } else if strain >= 10.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    }
+}
+
 fn get_recovery_color(score: i32) -> Color {
     if score >= 67 {
         Color::Green
@@ -0,0 +1,350 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::api::RateLimit;
+use crate::auth::reauth_cause;
+use crate::config::Config;
+use crate::format::is_too_new;
+use crate::data::{
+    BodyMeasurement, Cycle, DashboardData, HistoryWindow, JournalEntry, Profile, Recovery, Section, Sleep, Workout,
+};
+use crate::store::{Store, Stored};
+use crate::sync::SyncState;
+
+/// Where the dashboard gets its data from. The live API is one implementation;
+/// the others let the UI run without the network.
+#[async_trait]
+pub trait DataSource: Send + Sync {
+    /// Short name shown in the header, e.g. `live` or `demo`.
+    fn name(&self) -> &'static str;
+
+    async fn profile(&self) -> Result<Profile>;
+    async fn body_measurement(&self) -> Result<BodyMeasurement>;
+    async fn recovery(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Recovery>>;
+    async fn sleep(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Sleep>>;
+    async fn workouts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Workout>>;
+    async fn cycles(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Cycle>>;
+
+    /// Journal answers for cycles starting within the range. The API doesn't
+    /// expose the journal; imported answers go straight into the history store.
+    async fn journal(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        Ok(Vec::new())
+    }
+
+    /// Dashboard shown at startup; sources that cache may skip fetching.
+    async fn load(&self, window: HistoryWindow) -> Result<DashboardData> {
+        self.refresh(window).await
+    }
+
+    /// Fetch every section again.
+    async fn refresh(&self, window: HistoryWindow) -> Result<DashboardData> {
+        let mut store = Store::in_memory()?;
+        fetch_dashboard(self, window, DashboardData::default(), &mut store).await
+    }
+
+    /// Remaining request budget, for sources that talk to a rate-limited API.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+}
+
+/// Fetch every section of `source` concurrently on top of `base`, syncing the
+/// collections into `store` and reading the window back out of it. Collections
+/// are fetched incrementally from where the store's sync state left off. A
+/// failing section keeps its value from `base` and is reported in `errors`;
+/// lost authorization fails the whole dashboard instead.
+pub async fn fetch_dashboard<S: DataSource + ?Sized>(
+    source: &S,
+    window: HistoryWindow,
+    base: DashboardData,
+    store: &mut Store,
+) -> Result<DashboardData> {
+    let (start, end) = window.bounds();
+    let synced_at = Utc::now();
+    let mut sync = store.sync_state()?;
+    // Only a window reaching up to now can continue an incremental sync
+    let from = |section| if window.is_open() { sync.fetch_from(section, start) } else { start };
+    let froms = [Section::Recovery, Section::Sleep, Section::Workouts, Section::Cycles].map(from);
+
+    let (profile, body, recovery, sleep, workouts, cycles, journal) = tokio::join!(
+        source.profile(),
+        source.body_measurement(),
+        source.recovery(froms[0], end),
+        source.sleep(froms[1], end),
+        source.workouts(froms[2], end),
+        source.cycles(froms[3], end),
+        source.journal(start, end),
+    );
+
+    for error in [
+        profile.as_ref().err(),
+        body.as_ref().err(),
+        recovery.as_ref().err(),
+        sleep.as_ref().err(),
+        workouts.as_ref().err(),
+        cycles.as_ref().err(),
+    ].into_iter().flatten() {
+        if let Some(cause) = reauth_cause(error) {
+            return Err(cause.clone().into());
+        }
+    }
+
+    let mut data = DashboardData {
+        window,
+        errors: BTreeMap::new(),
+        ..base
+    };
+
+    let errors = &mut data.errors;
+    merge_section(errors, Section::Profile, profile.map(Some), &mut data.profile);
+    merge_section(errors, Section::Body, body.map(Some), &mut data.body);
+
+    let mut pass = SyncPass { store, sync: &mut sync, errors, synced_at, open: window.is_open() };
+    pass.store(froms[0], recovery)?;
+    pass.store(froms[1], sleep)?;
+    pass.store(froms[2], workouts)?;
+    pass.store(froms[3], cycles)?;
+    store.save_sync_state(&sync)?;
+    match journal {
+        Ok(entries) => {
+            store.upsert_journal(&entries)?;
+        }
+        Err(e) => {
+            data.errors.insert(Section::Journal, format!("{:#}", e));
+        }
+    }
+
+    read_window(store, &mut data)?;
+    if data.fetched_any() {
+        data.refreshed_at = Some(synced_at);
+    }
+    for section in Section::ALL {
+        if !data.errors.contains_key(&section) {
+            data.fetched_at.insert(section, synced_at);
+        }
+    }
+    Ok(data)
+}
+
+/// One refresh's worth of collection results on their way into the store.
+struct SyncPass<'a> {
+    store: &'a mut Store,
+    sync: &'a mut SyncState,
+    errors: &'a mut BTreeMap<Section, String>,
+    synced_at: DateTime<Utc>,
+    /// Whether the fetch reached up to now, so the sync state may advance
+    open: bool,
+}
+
+impl SyncPass<'_> {
+    fn store<T: Stored>(&mut self, from: DateTime<Utc>, result: Result<Vec<T>>) -> Result<()> {
+        match result {
+            Ok(records) => {
+                self.store.upsert(&records)?;
+                if self.open {
+                    self.sync.record(from, self.synced_at, &records);
+                }
+            }
+            Err(e) => {
+                self.errors.insert(T::SECTION, format!("{:#}", e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fill the collections of `data` with the stored records in its window.
+/// Unscored records and naps are left out; everything is listed newest first.
+pub fn read_window(store: &Store, data: &mut DashboardData) -> Result<()> {
+    let (start, end) = data.window.bounds();
+    read_range(store, data, start, end)
+}
+
+fn read_range(store: &Store, data: &mut DashboardData, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<()> {
+    data.recovery = store.records::<Recovery>(start, end)?;
+    data.recovery.retain(|r| r.score.is_some());
+    data.sleep = store.records::<Sleep>(start, end)?;
+    data.sleep.retain(|s| s.score.is_some() && !s.nap);
+    data.workouts = store.records::<Workout>(start, end)?;
+    data.workouts.retain(|w| w.score.is_some());
+    // Keep unscored cycles: the in-progress one may not have a score yet
+    data.cycles = store.records::<Cycle>(start, end)?;
+    data.journal = store.journal(start, end)?;
+    Ok(())
+}
+
+/// Store a section's fetch result, keeping the previous value on failure.
+fn merge_section<T>(errors: &mut BTreeMap<Section, String>, section: Section, result: Result<T>, slot: &mut T) {
+    match result {
+        Ok(value) => *slot = value,
+        Err(e) => {
+            errors.insert(section, format!("{:#}", e));
+        }
+    }
+}
+
+// ── Cache ───────────────────────────────────────────────
+
+/// Serves what's already on disk, never touching the network: profile and body
+/// measurements from `cache.json`, everything else from the history store.
+pub struct CacheSource {
+    config: Config,
+    /// Opened on first use and shared by every section
+    store: Mutex<Option<Store>>,
+}
+
+impl CacheSource {
+    pub fn new(config: Config) -> Self {
+        Self { config, store: Mutex::new(None) }
+    }
+
+    fn with_store<R>(&self, read: impl FnOnce(&Store) -> Result<R>) -> Result<R> {
+        let mut store = self.store.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let store = match &mut *store {
+            Some(store) => store,
+            empty => empty.insert(Store::open(&self.config)?),
+        };
+        read(store)
+    }
+
+    fn cached(&self) -> Result<DashboardData> {
+        self.config.load_cache().map_err(|e| {
+            if is_too_new(&e) { e } else { e.context("No cached data; run whoopterm --refresh while online first") }
+        })
+    }
+
+    fn stored<T: Stored>(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<T>> {
+        self.with_store(|store| store.records(start, end))
+    }
+}
+
+#[async_trait]
+impl DataSource for CacheSource {
+    fn name(&self) -> &'static str {
+        "cache"
+    }
+
+    async fn profile(&self) -> Result<Profile> {
+        self.cached()?.profile.context("Profile not cached")
+    }
+
+    async fn body_measurement(&self) -> Result<BodyMeasurement> {
+        self.cached()?.body.context("Body measurements not cached")
+    }
+
+    async fn recovery(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Recovery>> {
+        self.stored(start, end)
+    }
+
+    async fn sleep(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Sleep>> {
+        self.stored(start, end)
+    }
+
+    async fn workouts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Workout>> {
+        self.stored(start, end)
+    }
+
+    async fn cycles(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Cycle>> {
+        self.stored(start, end)
+    }
+
+    async fn journal(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        self.with_store(|store| store.journal(start, end))
+    }
+
+    // Nothing is fetched, so keep the cache's record of when each section was
+    // last fetched
+    async fn refresh(&self, window: HistoryWindow) -> Result<DashboardData> {
+        let mut data = DashboardData {
+            window,
+            offline: Some("offline mode".to_string()),
+            ..self.cached()?
+        };
+        self.with_store(|store| read_window(store, &mut data))?;
+        Ok(data)
+    }
+}
+
+// ── Fixtures ────────────────────────────────────────────
+
+/// Serves recorded API responses from a directory: `profile.json`, `body.json`,
+/// `recovery.json`, `sleep.json`, `workouts.json` and `cycles.json`. Collection
+/// files may hold either a bare array or an API page (`{"records": [...]}`).
+pub struct FixtureSource {
+    dir: PathBuf,
+}
+
+/// A recorded collection response.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Records<T> {
+    Page { records: Vec<T> },
+    List(Vec<T>),
+}
+
+impl FixtureSource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn read<T: DeserializeOwned>(&self, file: &str) -> Result<T> {
+        let path = self.dir.join(file);
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read fixture {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse fixture {}", path.display()))
+    }
+
+    fn read_records<T: DeserializeOwned>(&self, file: &str) -> Result<Vec<T>> {
+        Ok(match self.read::<Records<T>>(file)? {
+            Records::Page { records } | Records::List(records) => records,
+        })
+    }
+}
+
+#[async_trait]
+impl DataSource for FixtureSource {
+    fn name(&self) -> &'static str {
+        "fixtures"
+    }
+
+    async fn profile(&self) -> Result<Profile> {
+        self.read("profile.json")
+    }
+
+    async fn body_measurement(&self) -> Result<BodyMeasurement> {
+        self.read("body.json")
+    }
+
+    async fn recovery(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<Recovery>> {
+        self.read_records("recovery.json")
+    }
+
+    async fn sleep(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<Sleep>> {
+        self.read_records("sleep.json")
+    }
+
+    async fn workouts(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<Workout>> {
+        self.read_records("workouts.json")
+    }
+
+    async fn cycles(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<Cycle>> {
+        self.read_records("cycles.json")
+    }
+
+    // Recordings are replayed as-is, whatever window they were captured for,
+    // so everything they hold is read back rather than just the window
+    async fn refresh(&self, window: HistoryWindow) -> Result<DashboardData> {
+        let mut store = Store::in_memory()?;
+        let mut data = fetch_dashboard(self, window, DashboardData::default(), &mut store).await?;
+        read_range(&store, &mut data, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)?;
+        Ok(data)
+    }
+}
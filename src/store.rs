@@ -0,0 +1,494 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Months, Utc};
+use std::fmt;
+use std::str::FromStr;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::backfill::Checkpoint;
+use crate::config::{restrict, Config};
+use crate::data::{Cycle, JournalEntry, Recovery, Sleep, Workout};
+use crate::format::FormatError;
+use crate::sync::{Record, SyncState};
+
+/// Schema changes, oldest first; `MIGRATIONS[n]` takes a database from
+/// `user_version` n to n + 1. The first one is safe to run on stores created
+/// before the schema was versioned.
+const MIGRATIONS: &[&str] = &[SCHEMA_V1];
+
+const SCHEMA_V1: &str = "
+    CREATE TABLE IF NOT EXISTS recovery (
+        id TEXT PRIMARY KEY,
+        start INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS sleep (
+        id TEXT PRIMARY KEY,
+        start INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS workout (
+        id TEXT PRIMARY KEY,
+        start INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cycle (
+        id TEXT PRIMARY KEY,
+        start INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS recovery_start ON recovery (start);
+    CREATE INDEX IF NOT EXISTS sleep_start ON sleep (start);
+    CREATE INDEX IF NOT EXISTS workout_start ON workout (start);
+    CREATE INDEX IF NOT EXISTS cycle_start ON cycle (start);
+    CREATE TABLE IF NOT EXISTS journal (
+        cycle_start INTEGER NOT NULL,
+        question TEXT NOT NULL,
+        timezone_offset TEXT NOT NULL,
+        answered_yes INTEGER NOT NULL,
+        notes TEXT,
+        PRIMARY KEY (cycle_start, question)
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// Every recovery, sleep, workout and cycle ever synced, in `history.db` under
+/// the data directory. Records are kept as the API's JSON, keyed by WHOOP id,
+/// with their start and `updated_at` alongside for querying.
+pub struct Store {
+    conn: Connection,
+}
+
+/// A record kind with its own table in the store.
+pub trait Stored: Record + Serialize + DeserializeOwned {
+    const TABLE: &'static str;
+    /// How far apart the starts of two copies of the same record can be, when
+    /// one came from the API and the other from a WHOOP export
+    const MATCH_WINDOW: Duration;
+}
+
+impl Stored for Recovery {
+    const TABLE: &'static str = "recovery";
+    // The export only knows when the user woke up, not when the recovery was scored
+    const MATCH_WINDOW: Duration = Duration::hours(3);
+}
+
+impl Stored for Sleep {
+    const TABLE: &'static str = "sleep";
+    const MATCH_WINDOW: Duration = Duration::minutes(5);
+}
+
+impl Stored for Workout {
+    const TABLE: &'static str = "workout";
+    const MATCH_WINDOW: Duration = Duration::minutes(5);
+}
+
+impl Stored for Cycle {
+    const TABLE: &'static str = "cycle";
+    const MATCH_WINDOW: Duration = Duration::minutes(5);
+}
+
+/// The tables holding records, in the order they're reported.
+const TABLES: [&str; 4] = [Recovery::TABLE, Sleep::TABLE, Workout::TABLE, Cycle::TABLE];
+
+/// How much history to keep, e.g. `90d`, `12w`, `6m` or `2y`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Retention {
+    Days(u32),
+    Weeks(u32),
+    Months(u32),
+    Years(u32),
+}
+
+impl Retention {
+    /// Records starting before this are past retention.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let cutoff = match *self {
+            Retention::Days(n) => now.checked_sub_signed(Duration::days(n.into())),
+            Retention::Weeks(n) => now.checked_sub_signed(Duration::weeks(n.into())),
+            Retention::Months(n) => now.checked_sub_months(Months::new(n)),
+            Retention::Years(n) => now.checked_sub_months(Months::new(n.saturating_mul(12))),
+        };
+        cutoff.unwrap_or(DateTime::<Utc>::MIN_UTC)
+    }
+}
+
+impl FromStr for Retention {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a number followed by d, w, m or y (e.g. 2y), got {:?}", value);
+        let unit = value.chars().last().ok_or_else(invalid)?;
+        let count: u32 = value[..value.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+        if count == 0 {
+            return Err(invalid());
+        }
+        match unit {
+            'd' => Ok(Retention::Days(count)),
+            'w' => Ok(Retention::Weeks(count)),
+            'm' => Ok(Retention::Months(count)),
+            'y' => Ok(Retention::Years(count)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for Retention {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Retention> for String {
+    fn from(retention: Retention) -> Self {
+        retention.to_string()
+    }
+}
+
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Retention::Days(n) => write!(f, "{}d", n),
+            Retention::Weeks(n) => write!(f, "{}w", n),
+            Retention::Months(n) => write!(f, "{}m", n),
+            Retention::Years(n) => write!(f, "{}y", n),
+        }
+    }
+}
+
+/// Row count and span of one table.
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub table: &'static str,
+    pub records: usize,
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+}
+
+/// A problem found by `Store::verify`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub table: &'static str,
+    pub id: String,
+    pub problem: String,
+}
+
+/// Records imported from a WHOOP export have no WHOOP id, so they're stored
+/// under the negated Unix time of their start, which no real id can match.
+pub fn imported_id(start: DateTime<Utc>) -> i64 {
+    -start.timestamp()
+}
+
+fn is_imported(key: &str) -> bool {
+    key.starts_with('-')
+}
+
+impl Store {
+    pub fn open(config: &Config) -> Result<Self> {
+        let path = config.data_dir()?.join("history.db");
+        let conn = Connection::open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        restrict(&path)?;
+        Self::init(conn)
+    }
+
+    /// A throwaway store, for sources that don't keep history.
+    pub fn in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self> {
+        let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version as usize > MIGRATIONS.len() {
+            return Err(FormatError::TooNew {
+                file: "history.db".to_string(),
+                found: version,
+                supported: MIGRATIONS.len() as u32,
+            }.into());
+        }
+
+        for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)
+                .with_context(|| format!("Failed to migrate history store to version {}", from + 1))?;
+            tx.pragma_update(None, "user_version", from + 1)?;
+            tx.commit()?;
+        }
+        Ok(Self { conn })
+    }
+
+    /// Insert or update records, keeping whichever version has the later
+    /// `updated_at`. Records from the API replace imported copies of
+    /// themselves. Returns how many rows were written.
+    pub fn upsert<T: Stored>(&mut self, records: &[T]) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut written = 0;
+        {
+            let mut replace_imported = tx.prepare_cached(&format!(
+                "DELETE FROM {} WHERE id LIKE '-%' AND start BETWEEN ?1 AND ?2",
+                T::TABLE,
+            ))?;
+            let mut statement = tx.prepare_cached(&format!(
+                "INSERT INTO {table} (id, start, updated_at, data) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (id) DO UPDATE SET
+                     start = excluded.start, updated_at = excluded.updated_at, data = excluded.data
+                 WHERE excluded.updated_at >= {table}.updated_at",
+                table = T::TABLE,
+            ))?;
+            for record in records {
+                if !is_imported(&record.key()) {
+                    replace_imported.execute(params![
+                        (record.start() - T::MATCH_WINDOW).timestamp_millis(),
+                        (record.start() + T::MATCH_WINDOW).timestamp_millis(),
+                    ])?;
+                }
+                written += statement.execute(params![
+                    record.key(),
+                    record.start().timestamp_millis(),
+                    record.updated_at().timestamp_millis(),
+                    serde_json::to_string(record)?,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    /// Records starting within `[start, end)`, newest first.
+    pub fn records<T: Stored>(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<T>> {
+        let mut statement = self.conn.prepare_cached(&format!(
+            "SELECT data FROM {} WHERE start >= ?1 AND start < ?2 ORDER BY start DESC, id",
+            T::TABLE,
+        ))?;
+        let rows = statement.query_map(params![start.timestamp_millis(), end.timestamp_millis()], |row| {
+            row.get::<_, String>(0)
+        })?;
+
+        let mut records = Vec::new();
+        for json in rows {
+            let record = serde_json::from_str(&json?)
+                .with_context(|| format!("Corrupt {} record in history store", T::TABLE))?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Key of a stored record that starts close enough to `start` to be the
+    /// same one, if any.
+    pub fn find_near<T: Stored>(&self, start: DateTime<Utc>) -> Result<Option<String>> {
+        let mut statement = self.conn.prepare_cached(&format!(
+            "SELECT id FROM {} WHERE start BETWEEN ?1 AND ?2 ORDER BY abs(start - ?3) LIMIT 1",
+            T::TABLE,
+        ))?;
+        let key = statement
+            .query_row(
+                params![
+                    (start - T::MATCH_WINDOW).timestamp_millis(),
+                    (start + T::MATCH_WINDOW).timestamp_millis(),
+                    start.timestamp_millis(),
+                ],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(key)
+    }
+
+    /// Insert journal answers, replacing earlier answers to the same question
+    /// on the same day. Returns how many were new or changed.
+    pub fn upsert_journal(&mut self, entries: &[JournalEntry]) -> Result<usize> {
+        let tx = self.conn.transaction()?;
+        let mut written = 0;
+        {
+            let mut statement = tx.prepare_cached(
+                "INSERT INTO journal (cycle_start, question, timezone_offset, answered_yes, notes)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (cycle_start, question) DO UPDATE SET
+                     timezone_offset = excluded.timezone_offset,
+                     answered_yes = excluded.answered_yes,
+                     notes = excluded.notes
+                 WHERE journal.answered_yes IS NOT excluded.answered_yes
+                     OR journal.notes IS NOT excluded.notes",
+            )?;
+            for entry in entries {
+                written += statement.execute(params![
+                    entry.cycle_start.timestamp_millis(),
+                    entry.question,
+                    entry.timezone_offset,
+                    entry.answered_yes,
+                    entry.notes,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(written)
+    }
+
+    /// Journal answers for cycles starting within `[start, end)`, newest first.
+    pub fn journal(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT cycle_start, timezone_offset, question, answered_yes, notes FROM journal
+             WHERE cycle_start >= ?1 AND cycle_start < ?2
+             ORDER BY cycle_start DESC, question",
+        )?;
+        let rows = statement.query_map(params![start.timestamp_millis(), end.timestamp_millis()], |row| {
+            Ok(JournalEntry {
+                cycle_start: DateTime::from_timestamp_millis(row.get(0)?).unwrap_or_default(),
+                timezone_offset: row.get(1)?,
+                question: row.get(2)?,
+                answered_yes: row.get(3)?,
+                notes: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Counts and date spans of every table, the journal included.
+    pub fn stats(&self) -> Result<Vec<TableStats>> {
+        let tables = TABLES.iter().map(|table| (*table, "start")).chain([("journal", "cycle_start")]);
+        tables
+            .map(|(table, column)| {
+                let (records, first, last) = self.conn.query_row(
+                    &format!("SELECT count(*), min({column}), max({column}) FROM {table}"),
+                    [],
+                    |row| Ok((row.get::<_, usize>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, Option<i64>>(2)?)),
+                )?;
+                Ok(TableStats {
+                    table,
+                    records,
+                    first: first.and_then(DateTime::from_timestamp_millis),
+                    last: last.and_then(DateTime::from_timestamp_millis),
+                })
+            })
+            .collect()
+    }
+
+    /// Bytes the database takes on disk.
+    pub fn size(&self) -> Result<u64> {
+        let pages: u64 = self.conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: u64 = self.conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok(pages * page_size)
+    }
+
+    /// Delete everything that started before `cutoff`, returning how many rows
+    /// went from each table. Recoveries go with their sleep, which starts
+    /// hours before them. Syncs won't treat that range as already fetched.
+    pub fn prune(&mut self, cutoff: DateTime<Utc>) -> Result<Vec<(&'static str, usize)>> {
+        let tx = self.conn.transaction()?;
+        let mut deleted = Vec::new();
+        // A cutoff during the night would otherwise keep a recovery whose sleep is pruned
+        let with_sleep = tx.execute(
+            "DELETE FROM recovery WHERE json_valid(data)
+                 AND json_extract(data, '$.sleep_id') IN (SELECT id FROM sleep WHERE start < ?1)",
+            [cutoff.timestamp_millis()],
+        )?;
+        let tables = TABLES.iter().map(|table| (*table, "start")).chain([("journal", "cycle_start")]);
+        for (table, column) in tables {
+            let mut count = tx.execute(&format!("DELETE FROM {table} WHERE {column} < ?1"), [cutoff.timestamp_millis()])?;
+            if table == Recovery::TABLE {
+                count += with_sleep;
+            }
+            deleted.push((table, count));
+        }
+        tx.commit()?;
+
+        let mut sync = self.sync_state()?;
+        sync.forget_before(cutoff);
+        self.save_sync_state(&sync)?;
+        Ok(deleted)
+    }
+
+    /// Look for damage: a failing SQLite integrity check, records that no
+    /// longer parse, and recoveries pointing at a sleep that isn't stored.
+    pub fn verify(&self) -> Result<Vec<Issue>> {
+        let mut issues = Vec::new();
+        let integrity: Vec<String> = self.conn
+            .prepare("PRAGMA integrity_check")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        if integrity != ["ok"] {
+            issues.extend(integrity.into_iter().map(|problem| Issue { table: "history.db", id: String::new(), problem }));
+        }
+
+        issues.extend(self.unparseable::<Recovery>()?);
+        issues.extend(self.unparseable::<Sleep>()?);
+        issues.extend(self.unparseable::<Workout>()?);
+        issues.extend(self.unparseable::<Cycle>()?);
+
+        let mut orphans = self.conn.prepare(
+            "SELECT id, json_extract(data, '$.sleep_id') FROM recovery
+             WHERE json_valid(data) AND json_extract(data, '$.sleep_id') != ''
+                 AND NOT EXISTS (SELECT 1 FROM sleep WHERE sleep.id = json_extract(recovery.data, '$.sleep_id'))
+             ORDER BY start",
+        )?;
+        let rows = orphans.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (id, sleep_id) = row?;
+            issues.push(Issue { table: Recovery::TABLE, id, problem: format!("sleep {} is not stored", sleep_id) });
+        }
+        Ok(issues)
+    }
+
+    fn unparseable<T: Stored>(&self) -> Result<Vec<Issue>> {
+        let mut statement = self.conn.prepare(&format!("SELECT id, data FROM {} ORDER BY start", T::TABLE))?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        let mut issues = Vec::new();
+        for row in rows {
+            let (id, json) = row?;
+            if let Err(e) = serde_json::from_str::<T>(&json) {
+                issues.push(Issue { table: T::TABLE, id, problem: format!("unreadable record: {}", e) });
+            }
+        }
+        Ok(issues)
+    }
+
+    pub fn sync_state(&self) -> Result<SyncState> {
+        Ok(self.meta("sync")?.unwrap_or_default())
+    }
+
+    pub fn save_sync_state(&self, state: &SyncState) -> Result<()> {
+        self.set_meta("sync", state)
+    }
+
+    pub fn backfill_checkpoint(&self) -> Result<Checkpoint> {
+        Ok(self.meta("backfill")?.unwrap_or_default())
+    }
+
+    pub fn save_backfill_checkpoint(&self, checkpoint: &Checkpoint) -> Result<()> {
+        self.set_meta("backfill", checkpoint)
+    }
+
+    pub fn clear_backfill_checkpoint(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM meta WHERE key = 'backfill'", [])?;
+        Ok(())
+    }
+
+    fn meta<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let value: Option<String> = self.conn
+            .query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?;
+        value
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .with_context(|| format!("Corrupt {} entry in history store", key))
+    }
+
+    fn set_meta<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![key, serde_json::to_string(value)?],
+        )?;
+        Ok(())
+    }
+}
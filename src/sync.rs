@@ -0,0 +1,168 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::data::{Cycle, Recovery, Section, Sleep, Workout};
+
+/// How far before the last sync an incremental fetch starts. The API filters on
+/// a record's start, and a night's sleep only shows up once it has ended, so
+/// anything that began up to a day before the last sync may be new.
+const LOOKBACK: Duration = Duration::days(1);
+
+/// Bookkeeping that lets a refresh fetch only what changed since the last one.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    #[serde(default)]
+    pub collections: BTreeMap<Section, CollectionSync>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSync {
+    /// When the last successful fetch of this collection started
+    pub last_sync: DateTime<Utc>,
+    /// Everything from here up to `last_sync` has been fetched
+    pub synced_since: DateTime<Utc>,
+    /// Start of the oldest record that was still pending (or, for cycles,
+    /// still open) at that point and has to be fetched again
+    #[serde(default)]
+    pub recheck_from: Option<DateTime<Utc>>,
+}
+
+impl SyncState {
+    /// Where a fetch of `section` reaching up to now has to start so that,
+    /// together with what was synced before, nothing since `floor` is missing.
+    pub fn fetch_from(&self, section: Section, floor: DateTime<Utc>) -> DateTime<Utc> {
+        let Some(sync) = self.collections.get(&section) else {
+            return floor;
+        };
+        if floor < sync.synced_since {
+            return floor;
+        }
+        let since = sync.last_sync - LOOKBACK;
+        let from = sync.recheck_from.map_or(since, |recheck| recheck.min(since));
+        from.max(floor)
+    }
+
+    /// Stop counting anything before `cutoff` as synced, e.g. because it was
+    /// pruned from the store, so windows reaching back there fetch it again.
+    pub fn forget_before(&mut self, cutoff: DateTime<Utc>) {
+        for sync in self.collections.values_mut() {
+            sync.synced_since = sync.synced_since.max(cutoff);
+        }
+    }
+
+    /// Remember a successful fetch of `[from, now)` that started at `synced_at`.
+    pub fn record<T: Record>(&mut self, from: DateTime<Utc>, synced_at: DateTime<Utc>, fetched: &[T]) {
+        let synced_since = match self.collections.get(&T::SECTION) {
+            Some(previous) => previous.synced_since.min(from),
+            None => from,
+        };
+        let recheck_from = fetched.iter().filter(|r| r.needs_recheck()).map(Record::start).min();
+        self.collections.insert(T::SECTION, CollectionSync { last_sync: synced_at, synced_since, recheck_from });
+    }
+}
+
+/// A synced WHOOP record: identified by id and versioned by `updated_at`.
+pub trait Record {
+    const SECTION: Section;
+    /// Collection endpoint the records are fetched from
+    const ENDPOINT: &'static str;
+
+    fn key(&self) -> String;
+    fn updated_at(&self) -> DateTime<Utc>;
+    /// The time the API's `start`/`end` filters apply to
+    fn start(&self) -> DateTime<Utc>;
+
+    /// Whether the record may still change, so a later sync must look at it again.
+    fn needs_recheck(&self) -> bool;
+}
+
+fn is_pending(score_state: &str) -> bool {
+    score_state == "PENDING_SCORE"
+}
+
+impl Record for Recovery {
+    const SECTION: Section = Section::Recovery;
+    const ENDPOINT: &'static str = "/v2/recovery";
+
+    // Recoveries have no id of their own; there is one per cycle
+    fn key(&self) -> String {
+        self.cycle_id.to_string()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    fn needs_recheck(&self) -> bool {
+        is_pending(&self.score_state)
+    }
+}
+
+impl Record for Sleep {
+    const SECTION: Section = Section::Sleep;
+    const ENDPOINT: &'static str = "/v2/activity/sleep";
+
+    fn key(&self) -> String {
+        self.id.clone()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    fn needs_recheck(&self) -> bool {
+        is_pending(&self.score_state)
+    }
+}
+
+impl Record for Workout {
+    const SECTION: Section = Section::Workouts;
+    const ENDPOINT: &'static str = "/v2/activity/workout";
+
+    fn key(&self) -> String {
+        self.id.clone()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    fn needs_recheck(&self) -> bool {
+        is_pending(&self.score_state)
+    }
+}
+
+impl Record for Cycle {
+    const SECTION: Section = Section::Cycles;
+    const ENDPOINT: &'static str = "/v2/cycle";
+
+    fn key(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn updated_at(&self) -> DateTime<Utc> {
+        self.updated_at
+    }
+
+    fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    // The current cycle keeps accumulating strain until it ends
+    fn needs_recheck(&self) -> bool {
+        is_pending(&self.score_state) || self.end.is_none()
+    }
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use chrono::{DateTime, DurationRound, Utc};
+use common::{page, token_response, workout, Stub, TOKEN_PATH};
+use serde_json::{json, Value};
+use whoopterm::api::WhoopAPI;
+use whoopterm::auth::reauth_cause;
+use whoopterm::config::Config;
+use whoopterm::data::{HistoryWindow, Section};
+use whoopterm::source::DataSource;
+use wiremock::matchers::{body_string_contains, header, method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn refresh_loads_every_section() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+    stub.mount_dashboard().await;
+
+    let data = WhoopAPI::with_config(stub.config()).refresh_all_data(HistoryWindow::default()).await.unwrap();
+
+    assert!(data.errors.is_empty(), "unexpected errors: {:?}", data.errors);
+    assert_eq!(data.profile.unwrap().first_name, "Jane");
+    assert_eq!(data.body.unwrap().max_heart_rate, 200);
+    assert_eq!(data.recovery.len(), 1);
+    assert_eq!(data.sleep.len(), 1);
+    assert_eq!(data.workouts.len(), 1);
+    assert_eq!(data.cycles.len(), 1);
+    assert!(data.cycles[0].end.is_none());
+}
+
+#[tokio::test]
+async fn collections_follow_next_token() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .and(query_param("limit", "25"))
+        .and(query_param_is_missing("nextToken"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![workout("w1"), workout("w2")], Some("page-2"))))
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .and(query_param("nextToken", "page-2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![workout("w3")], None)))
+        .mount(&stub.server)
+        .await;
+    stub.mount_dashboard().await;
+
+    let data = WhoopAPI::with_config(stub.config()).refresh_all_data(HistoryWindow::default()).await.unwrap();
+
+    let ids: Vec<_> = data.workouts.iter().map(|w| w.id.as_str()).collect();
+    assert_eq!(ids, ["w1", "w2", "w3"]);
+}
+
+#[tokio::test]
+async fn failing_section_does_not_discard_others() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+        .mount(&stub.server)
+        .await;
+    stub.mount_dashboard().await;
+
+    let data = WhoopAPI::with_config(stub.config()).refresh_all_data(HistoryWindow::default()).await.unwrap();
+
+    assert_eq!(data.errors.keys().collect::<Vec<_>>(), [&Section::Workouts]);
+    assert!(data.workouts.is_empty());
+    assert_eq!(data.recovery.len(), 1);
+    assert_eq!(data.sleep.len(), 1);
+}
+
+#[tokio::test]
+async fn sections_that_failed_are_retried_before_the_cache_expires() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+        .mount(&stub.server)
+        .await;
+    stub.mount_dashboard().await;
+    let api = WhoopAPI::with_config(stub.config());
+    api.refresh_all_data(HistoryWindow::default()).await.unwrap();
+
+    stub.server.reset().await;
+    stub.mount_dashboard().await;
+    let data = api.load_cached_or_refresh(HistoryWindow::default()).await.unwrap();
+
+    assert!(data.errors.is_empty(), "unexpected errors: {:?}", data.errors);
+    assert_eq!(data.workouts.len(), 1);
+}
+
+#[tokio::test]
+async fn refresh_is_incremental_and_rechecks_pending_records() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+
+    let now = Utc::now().duration_trunc(chrono::Duration::seconds(1)).unwrap();
+    let pending_start = now - chrono::Duration::days(3);
+    let mut scored = workout("w1");
+    scored["start"] = json!(now - chrono::Duration::days(5));
+    let mut pending = workout("w2");
+    pending["start"] = json!(pending_start);
+    pending["score_state"] = json!("PENDING_SCORE");
+    pending["score"] = Value::Null;
+    let mut rescored = workout("w2");
+    rescored["start"] = json!(pending_start);
+    rescored["updated_at"] = json!(now);
+
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![scored, pending], None)))
+        .up_to_n_times(1)
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![rescored], None)))
+        .mount(&stub.server)
+        .await;
+    stub.mount_dashboard().await;
+
+    let api = WhoopAPI::with_config(stub.config());
+    let first = api.refresh_all_data(HistoryWindow::default()).await.unwrap();
+    let second = api.refresh_all_data(HistoryWindow::default()).await.unwrap();
+
+    let ids = |workouts: &[whoopterm::data::Workout]| workouts.iter().map(|w| w.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&first.workouts), ["w1"]);
+    assert_eq!(ids(&second.workouts), ["w2", "w1"]);
+
+    // The second fetch only goes back as far as the pending record
+    let requests = stub.server.received_requests().await.unwrap();
+    let starts: Vec<DateTime<Utc>> = requests
+        .iter()
+        .filter(|r| r.url.path() == "/v2/activity/workout")
+        .map(|r| {
+            let (_, start) = r.url.query_pairs().find(|(k, _)| k == "start").unwrap();
+            start.parse().unwrap()
+        })
+        .collect();
+    assert_eq!(starts.len(), 2);
+    assert!(starts[0] < now - chrono::Duration::days(6));
+    assert_eq!(starts[1], pending_start);
+}
+
+#[tokio::test]
+async fn rate_limited_request_is_retried() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+
+    Mock::given(method("GET"))
+        .and(path("/v2/user/profile/basic"))
+        .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/user/profile/basic"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(common::profile())
+                .insert_header("X-RateLimit-Limit", "100, 100;window=60")
+                .insert_header("X-RateLimit-Remaining", "97")
+                .insert_header("X-RateLimit-Reset", "42"),
+        )
+        .mount(&stub.server)
+        .await;
+
+    let api = WhoopAPI::with_config(stub.config());
+    api.test_connection().await.unwrap();
+
+    let rate_limit = api.rate_limit().expect("rate limit headers recorded");
+    assert_eq!(rate_limit.limit, Some(100));
+    assert_eq!(rate_limit.remaining, Some(97));
+    assert_eq!(rate_limit.reset, Some(std::time::Duration::from_secs(42)));
+}
+
+#[tokio::test]
+async fn unauthorized_request_refreshes_token_and_retries() {
+    let stub = Stub::start().await;
+    stub.save_tokens("revoked", chrono::Duration::hours(1));
+
+    Mock::given(method("GET"))
+        .and(path("/v2/user/profile/basic"))
+        .and(header("authorization", "Bearer fresh"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(common::profile()))
+        .with_priority(1)
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/user/profile/basic"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .respond_with(token_response("fresh", "refresh-2"))
+        .expect(1)
+        .mount(&stub.server)
+        .await;
+
+    WhoopAPI::with_config(stub.config()).test_connection().await.unwrap();
+
+    let tokens = stub.config().load_tokens().unwrap();
+    assert_eq!(tokens.access_token, "fresh");
+    assert_eq!(tokens.refresh_token.as_deref(), Some("refresh-2"));
+}
+
+#[tokio::test]
+async fn rejected_refresh_requires_reauthentication() {
+    let stub = Stub::start().await;
+    stub.save_tokens("revoked", chrono::Duration::hours(1));
+    stub.mount_dashboard().await;
+
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .with_priority(1)
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .respond_with(ResponseTemplate::new(400).set_body_json(serde_json::json!({ "error": "invalid_grant" })))
+        .mount(&stub.server)
+        .await;
+
+    let error = WhoopAPI::with_config(stub.config()).refresh_all_data(HistoryWindow::default()).await.unwrap_err();
+
+    assert!(reauth_cause(&error).is_some(), "expected re-auth error, got {:#}", error);
+}
+
+#[tokio::test]
+async fn token_endpoint_outage_does_not_require_reauthentication() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", -chrono::Duration::minutes(1));
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .respond_with(ResponseTemplate::new(502).set_body_string("<html>Bad Gateway</html>"))
+        .mount(&stub.server)
+        .await;
+
+    let error = WhoopAPI::with_config(stub.config()).test_connection().await.unwrap_err();
+
+    assert!(reauth_cause(&error).is_none(), "unexpected re-auth error: {:#}", error);
+    assert!(stub.config().load_tokens().unwrap().refresh_token.is_some(), "tokens are kept");
+}
+
+#[tokio::test]
+async fn unreachable_api_falls_back_to_local_data() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+    stub.mount_dashboard().await;
+    let online = WhoopAPI::with_config(stub.config()).refresh(HistoryWindow::default()).await.unwrap();
+    assert!(online.offline.is_none());
+
+    // The token needs refreshing, but nothing is listening at the token endpoint
+    stub.save_tokens("access-1", -chrono::Duration::minutes(1));
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let config = Config { token_url: Some(format!("http://{}/token", closed)), ..stub.config() };
+    let data = WhoopAPI::with_config(config).refresh(HistoryWindow::default()).await.unwrap();
+
+    assert!(data.offline.is_some());
+    assert!(data.errors.is_empty(), "unexpected errors: {:?}", data.errors);
+    assert_eq!(data.sleep.len(), online.sleep.len());
+    assert_eq!(data.fetched_at, online.fetched_at);
+    assert!(stub.config().load_tokens().unwrap().refresh_token.is_some(), "tokens are kept");
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use common::{token_response, Stub, TOKEN_PATH};
+use oauth2::url::Url;
+use whoopterm::auth::{parse_callback, parse_pasted, receive_callback, serve_callback, AuthError, AuthManager, CallbackError};
+use whoopterm::config::Config;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::Mock;
+
+#[tokio::test]
+async fn valid_token_is_used_without_refreshing() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+
+    let token = AuthManager::with_config(stub.config()).get_access_token().await.unwrap();
+
+    assert_eq!(token, "access-1");
+    assert!(stub.server.received_requests().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn expired_token_is_refreshed_and_persisted() {
+    let stub = Stub::start().await;
+    stub.save_tokens("expired", -chrono::Duration::minutes(1));
+
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .and(body_string_contains("refresh_token=refresh-1"))
+        .respond_with(token_response("access-2", "refresh-2"))
+        .expect(1)
+        .mount(&stub.server)
+        .await;
+
+    let token = AuthManager::with_config(stub.config()).get_access_token().await.unwrap();
+
+    assert_eq!(token, "access-2");
+    let stored = stub.config().load_tokens().unwrap();
+    assert_eq!(stored.access_token, "access-2");
+    assert_eq!(stored.refresh_token.as_deref(), Some("refresh-2"));
+    assert!(stored.expires_at > chrono::Utc::now());
+}
+
+#[tokio::test]
+async fn missing_tokens_means_not_authenticated() {
+    let stub = Stub::start().await;
+
+    let error = AuthManager::with_config(stub.config()).get_access_token().await.unwrap_err();
+
+    assert!(matches!(error.downcast_ref::<AuthError>(), Some(AuthError::NotAuthenticated)));
+}
+
+#[test]
+fn callback_code_is_decoded_and_state_checked() {
+    let url = |query: &str| Url::parse(&format!("http://localhost:8080/callback?{}", query)).unwrap();
+
+    assert_eq!(parse_callback(&url("code=abc%2F123&state=s1"), "s1"), Ok("abc/123".to_string()));
+    assert_eq!(parse_callback(&url("code=abc&state=forged"), "s1"), Err(CallbackError::StateMismatch));
+    assert_eq!(parse_callback(&url("code=abc"), "s1"), Err(CallbackError::StateMismatch));
+    assert_eq!(parse_callback(&url("state=s1"), "s1"), Err(CallbackError::MissingCode));
+}
+
+#[test]
+fn denied_login_reports_the_provider_error() {
+    let url = Url::parse("http://localhost:8080/callback?error=access_denied&error_description=The+user+denied+access&state=s1").unwrap();
+
+    let error = parse_callback(&url, "s1").unwrap_err();
+
+    assert_eq!(error, CallbackError::Denied {
+        error: "access_denied".to_string(),
+        description: Some("The user denied access".to_string()),
+    });
+    assert!(error.to_string().contains("The user denied access"));
+}
+
+#[test]
+fn pasted_redirect_or_bare_code_is_accepted() {
+    assert_eq!(parse_pasted("http://localhost:8080/callback?code=abc&state=s1\n", "s1"), Ok("abc".to_string()));
+    assert_eq!(parse_pasted("localhost:8080/callback?state=s1&code=abc", "s1"), Ok("abc".to_string()));
+    assert_eq!(parse_pasted("  abc123\n", "s1"), Ok("abc123".to_string()));
+    assert_eq!(parse_pasted("http://localhost:8080/callback?code=abc&state=old", "s1"), Err(CallbackError::StateMismatch));
+    assert_eq!(parse_pasted("not a code", "s1"), Err(CallbackError::MissingCode));
+}
+
+#[tokio::test]
+async fn callback_server_skips_stray_requests_and_answers_with_a_page() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let redirect = Url::parse(&format!("{}/callback", base)).unwrap();
+    let server = tokio::spawn(async move { serve_callback(listener, &redirect, "s1").await });
+    let client = reqwest::Client::new();
+
+    let favicon = client.get(format!("{}/favicon.ico", base)).send().await.unwrap();
+    assert_eq!(favicon.status(), 404);
+    let bare = client.get(format!("{}/callback", base)).send().await.unwrap();
+    assert_eq!(bare.status(), 404);
+
+    let login = client.get(format!("{}/callback?code=code-1&state=s1", base)).send().await.unwrap();
+    assert_eq!(login.status(), 200);
+    assert_eq!(login.headers()["content-type"], "text/html; charset=utf-8");
+    let length: usize = login.headers()["content-length"].to_str().unwrap().parse().unwrap();
+    let page = login.text().await.unwrap();
+    assert_eq!(page.len(), length);
+    assert!(page.contains("Logged in"), "{}", page);
+    assert_eq!(server.await.unwrap().unwrap(), "code-1");
+}
+
+#[tokio::test]
+async fn idle_connections_do_not_hold_up_the_login() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let redirect = Url::parse(&format!("http://{}/callback", address)).unwrap();
+    let server = tokio::spawn(async move { serve_callback(listener, &redirect, "s1").await });
+
+    // Preconnects the browser never sends a request on
+    let _idle = [
+        tokio::net::TcpStream::connect(address).await.unwrap(),
+        tokio::net::TcpStream::connect(address).await.unwrap(),
+    ];
+    let started = std::time::Instant::now();
+    let login = reqwest::get(format!("http://{}/callback?code=code-1&state=s1", address)).await.unwrap();
+
+    assert_eq!(login.status(), 200);
+    assert_eq!(server.await.unwrap().unwrap(), "code-1");
+    assert!(started.elapsed() < std::time::Duration::from_secs(2), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn failed_login_page_shows_the_escaped_reason() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let redirect = Url::parse(&format!("{}/callback", base)).unwrap();
+    let server = tokio::spawn(async move { serve_callback(listener, &redirect, "s1").await });
+
+    let url = format!("{}/callback?error=access_denied&error_description=%3Cscript%3E&state=s1", base);
+    let response = reqwest::get(url).await.unwrap();
+
+    assert_eq!(response.status(), 400);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("access_denied") && page.contains("&lt;script&gt;"), "{}", page);
+    let error = server.await.unwrap().unwrap_err();
+    assert!(matches!(error.downcast_ref(), Some(CallbackError::Denied { .. })), "{:#}", error);
+}
+
+#[tokio::test]
+async fn waiting_for_the_login_gives_up_after_the_timeout() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let redirect = Url::parse(&format!("http://{}/callback", listener.local_addr().unwrap())).unwrap();
+    let timeout = Config { login_timeout: Some(1), ..Default::default() }.login_timeout();
+
+    let started = std::time::Instant::now();
+    let error = receive_callback(listener, &redirect, "s1", timeout).await.unwrap_err();
+
+    assert!(error.to_string().contains("No login arrived within 1 seconds"), "{:#}", error);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+/// The port after `flag` in a busy-port message, checked to be free.
+fn suggested_port(message: &str, flag: &str) -> u16 {
+    let value = message.split(flag).nth(1).and_then(|rest| rest.split_whitespace().next()).expect(message);
+    let port = Url::parse(value).ok().and_then(|url| url.port()).unwrap_or_else(|| value.parse().expect(message));
+    assert!(std::net::TcpListener::bind(("127.0.0.1", port)).is_ok(), "port {} is free", port);
+    port
+}
+
+#[tokio::test]
+async fn busy_callback_port_suggests_a_free_one_through_the_setting_in_use() {
+    let stub = Stub::start().await;
+    let busy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = busy.local_addr().unwrap().port();
+
+    let redirect = Config { redirect_uri: Some(format!("http://localhost:{}/callback", port)), ..stub.config() };
+    let error = AuthManager::with_config(redirect).authenticate().await.unwrap_err().to_string();
+    assert!(error.contains(&format!("Port {}", port)) && error.contains("developer-dashboard"), "{}", error);
+    assert_ne!(suggested_port(&error, "--redirect-uri "), port);
+
+    let explicit = Config { callback_port: Some(port), ..stub.config() };
+    let error = AuthManager::with_config(explicit).authenticate().await.unwrap_err().to_string();
+    assert!(!error.contains("--redirect-uri"), "{}", error);
+    assert_ne!(suggested_port(&error, "--callback-port "), port);
+}
+
+#[tokio::test]
+async fn login_uses_pkce() {
+    let stub = Stub::start().await;
+    Mock::given(method("POST"))
+        .and(path(TOKEN_PATH))
+        .and(body_string_contains("grant_type=authorization_code"))
+        .and(body_string_contains("code=code-1"))
+        .and(body_string_contains("code_verifier="))
+        .respond_with(token_response("access-1", "refresh-1"))
+        .expect(1)
+        .mount(&stub.server)
+        .await;
+    let auth = AuthManager::with_config(stub.config());
+
+    let login = auth.start_login().unwrap();
+    let query: std::collections::HashMap<_, _> = login.url.query_pairs().into_owned().collect();
+    assert_eq!(query.get("code_challenge_method").map(String::as_str), Some("S256"));
+    assert_eq!(query.get("code_challenge").map(String::len), Some(43));
+    assert_eq!(query.get("state").map(String::as_str), Some(login.state()));
+    auth.finish_login(login, "code-1".to_string()).await.unwrap();
+
+    assert_eq!(stub.config().load_tokens().unwrap().access_token, "access-1");
+}
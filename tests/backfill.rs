@@ -0,0 +1,92 @@
+mod common;
+
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use common::{cycle, page, recovery, sleep, workout, Stub};
+use serde_json::json;
+use whoopterm::api::WhoopAPI;
+use whoopterm::backfill::backfill;
+use whoopterm::data::{HistoryWindow, Section, Workout};
+use whoopterm::store::Store;
+use wiremock::matchers::{method, path, query_param, query_param_is_missing};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn mount_collections(stub: &Stub) {
+    stub.mount_json("/v2/recovery", page(vec![recovery(1), recovery(2)], None)).await;
+    stub.mount_json("/v2/activity/sleep", page(vec![sleep("s1")], None)).await;
+    stub.mount_json("/v2/cycle", page(vec![cycle(1)], None)).await;
+}
+
+#[tokio::test]
+async fn backfill_walks_every_page_and_counts_records() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", Duration::hours(1));
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .and(query_param_is_missing("nextToken"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![workout("w1"), workout("w2")], Some("page-2"))))
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .and(query_param("nextToken", "page-2"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![workout("w3")], None)))
+        .mount(&stub.server)
+        .await;
+    mount_collections(&stub).await;
+
+    let api = WhoopAPI::with_config(stub.config());
+    let mut store = Store::open(&stub.config()).unwrap();
+    let checkpoint = backfill(&api, &mut store, |_, _| {}).await.unwrap();
+
+    let fetched: Vec<_> = checkpoint.collections.iter().map(|(s, p)| (*s, p.fetched)).collect();
+    assert_eq!(fetched, [(Section::Recovery, 2), (Section::Sleep, 1), (Section::Workouts, 3), (Section::Cycles, 1)]);
+    assert!(checkpoint.collections.values().all(|p| p.done));
+
+    let (start, end) = HistoryWindow::Days(7).bounds();
+    assert_eq!(store.records::<Workout>(start, end).unwrap().len(), 3);
+    // Finished backfills aren't resumed
+    assert!(store.backfill_checkpoint().unwrap().collections.is_empty());
+}
+
+#[tokio::test]
+async fn interrupted_backfill_resumes_where_it_stopped() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", Duration::hours(1));
+
+    let oldest = Utc::now().duration_trunc(Duration::seconds(1)).unwrap() - Duration::days(30);
+    let mut older = workout("w2");
+    older["start"] = json!(oldest);
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .and(query_param_is_missing("nextToken"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(page(vec![workout("w1"), older], Some("page-2"))))
+        .mount(&stub.server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/v2/activity/workout"))
+        .and(query_param("nextToken", "page-2"))
+        .respond_with(ResponseTemplate::new(400).set_body_string("bad request"))
+        .mount(&stub.server)
+        .await;
+    mount_collections(&stub).await;
+
+    let api = WhoopAPI::with_config(stub.config());
+    let mut store = Store::open(&stub.config()).unwrap();
+    let error = backfill(&api, &mut store, |_, _| {}).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("run it again to resume"));
+
+    stub.server.reset().await;
+    stub.mount_json("/v2/activity/workout", page(vec![workout("w3")], None)).await;
+    mount_collections(&stub).await;
+
+    let checkpoint = backfill(&api, &mut store, |_, _| {}).await.unwrap();
+    assert_eq!(checkpoint.collections[&Section::Workouts].fetched, 3);
+
+    // Finished collections aren't fetched again, and workouts pick up below
+    // the oldest record already stored
+    let requests = stub.server.received_requests().await.unwrap();
+    let paths: Vec<_> = requests.iter().map(|r| r.url.path()).collect();
+    assert_eq!(paths, ["/v2/activity/workout", "/v2/cycle"]);
+    let (_, end) = requests[0].url.query_pairs().find(|(k, _)| k == "end").unwrap();
+    assert_eq!(end.parse::<DateTime<Utc>>().unwrap(), oldest + Duration::milliseconds(1));
+}
@@ -0,0 +1,191 @@
+//! Local stand-in for the WHOOP API and OAuth endpoints, plus record fixtures.
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use tempfile::TempDir;
+use whoopterm::auth::Tokens;
+use whoopterm::config::Config;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const TOKEN_PATH: &str = "/oauth/oauth2/token";
+
+pub struct Stub {
+    pub server: MockServer,
+    pub dir: TempDir,
+}
+
+impl Stub {
+    pub async fn start() -> Self {
+        Self {
+            server: MockServer::start().await,
+            dir: TempDir::new().expect("temp dir"),
+        }
+    }
+
+    /// Config pointing every endpoint and the data directory at this stub.
+    pub fn config(&self) -> Config {
+        Config {
+            client_id: Some("client-id".to_string()),
+            client_secret: Some("client-secret".to_string()),
+            api_base: Some(self.server.uri()),
+            auth_url: Some(format!("{}/oauth/oauth2/auth", self.server.uri())),
+            token_url: Some(format!("{}{}", self.server.uri(), TOKEN_PATH)),
+            data_dir: Some(self.dir.path().to_path_buf()),
+            ..Default::default()
+        }
+    }
+
+    pub fn save_tokens(&self, access_token: &str, expires_in: chrono::Duration) {
+        self.config()
+            .save_tokens(&Tokens {
+                access_token: access_token.to_string(),
+                refresh_token: Some("refresh-1".to_string()),
+                expires_at: chrono::Utc::now() + expires_in,
+            })
+            .expect("save tokens");
+    }
+
+    pub async fn mount_json(&self, endpoint: &str, body: Value) {
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Serve every dashboard endpoint with one record each.
+    pub async fn mount_dashboard(&self) {
+        self.mount_json("/v2/user/profile/basic", profile()).await;
+        self.mount_json("/v2/user/measurement/body", body_measurement()).await;
+        self.mount_json("/v2/recovery", page(vec![recovery(1)], None)).await;
+        self.mount_json("/v2/activity/sleep", page(vec![sleep("s1")], None)).await;
+        self.mount_json("/v2/activity/workout", page(vec![workout("w1")], None)).await;
+        self.mount_json("/v2/cycle", page(vec![cycle(1)], None)).await;
+    }
+}
+
+pub fn token_response(access_token: &str, refresh_token: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({
+        "access_token": access_token,
+        "refresh_token": refresh_token,
+        "token_type": "bearer",
+        "expires_in": 3600,
+    }))
+}
+
+pub fn page(records: Vec<Value>, next_token: Option<&str>) -> Value {
+    match next_token {
+        Some(token) => json!({ "records": records, "next_token": token }),
+        None => json!({ "records": records }),
+    }
+}
+
+/// A timestamp `hours` before now, so records fall inside the default window.
+/// "Now" is fixed once per test run so fixtures built moments apart still
+/// share a start, and sort by id as a tie-breaker.
+pub fn hours_ago(hours: i64) -> String {
+    static NOW: std::sync::OnceLock<chrono::DateTime<chrono::Utc>> = std::sync::OnceLock::new();
+    (*NOW.get_or_init(chrono::Utc::now) - chrono::Duration::hours(hours)).to_rfc3339()
+}
+
+pub fn profile() -> Value {
+    json!({ "user_id": 10129, "email": "jane@example.com", "first_name": "Jane", "last_name": "Doe" })
+}
+
+pub fn body_measurement() -> Value {
+    json!({ "height_meter": 1.8288, "weight_kilogram": 90.7185, "max_heart_rate": 200 })
+}
+
+pub fn recovery(cycle_id: i64) -> Value {
+    json!({
+        "cycle_id": cycle_id,
+        "sleep_id": format!("sleep-{}", cycle_id),
+        "user_id": 10129,
+        "created_at": hours_ago(20),
+        "updated_at": hours_ago(17),
+        "score_state": "SCORED",
+        "score": {
+            "user_calibrating": false,
+            "recovery_score": 44.0,
+            "resting_heart_rate": 64.0,
+            "hrv_rmssd_milli": 31.8
+        }
+    })
+}
+
+pub fn sleep(id: &str) -> Value {
+    json!({
+        "id": id,
+        "cycle_id": 1,
+        "user_id": 10129,
+        "created_at": hours_ago(20),
+        "updated_at": hours_ago(17),
+        "start": hours_ago(29),
+        "end": hours_ago(21),
+        "timezone_offset": "-05:00",
+        "nap": false,
+        "score_state": "SCORED",
+        "score": {
+            "stage_summary": {
+                "total_in_bed_time_milli": 28800000,
+                "total_awake_time_milli": 1800000,
+                "total_no_data_time_milli": 0,
+                "total_light_sleep_time_milli": 14400000,
+                "total_slow_wave_sleep_time_milli": 5400000,
+                "total_rem_sleep_time_milli": 7200000,
+                "sleep_cycle_count": 4,
+                "disturbance_count": 10
+            },
+            "sleep_needed": {
+                "baseline_milli": 27000000,
+                "need_from_sleep_debt_milli": 0,
+                "need_from_recent_strain_milli": 0,
+                "need_from_recent_nap_milli": 0
+            },
+            "sleep_efficiency_percentage": 92.0
+        }
+    })
+}
+
+pub fn workout(id: &str) -> Value {
+    json!({
+        "id": id,
+        "user_id": 10129,
+        "created_at": hours_ago(8),
+        "updated_at": hours_ago(7),
+        "start": hours_ago(10),
+        "end": hours_ago(9),
+        "timezone_offset": "-05:00",
+        "sport_name": "running",
+        "score_state": "SCORED",
+        "score": {
+            "strain": 8.2,
+            "average_heart_rate": 123,
+            "max_heart_rate": 146,
+            "kilojoule": 1569.3,
+            "percent_recorded": 100.0,
+            "zone_durations": {
+                "zone_zero_milli": 0,
+                "zone_one_milli": 600000,
+                "zone_two_milli": 1200000,
+                "zone_three_milli": 1200000,
+                "zone_four_milli": 600000,
+                "zone_five_milli": 0
+            }
+        }
+    })
+}
+
+pub fn cycle(id: i64) -> Value {
+    json!({
+        "id": id,
+        "user_id": 10129,
+        "created_at": hours_ago(20),
+        "updated_at": hours_ago(17),
+        "start": hours_ago(29),
+        "timezone_offset": "-05:00",
+        "score_state": "SCORED",
+        "score": { "strain": 5.3, "kilojoule": 8288.3, "average_heart_rate": 68, "max_heart_rate": 141 }
+    })
+}
@@ -0,0 +1,200 @@
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+use tempfile::TempDir;
+use whoopterm::auth::Tokens;
+use whoopterm::config::{Config, Origin};
+
+#[cfg(unix)]
+fn mode(path: &std::path::Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+fn tokens() -> Tokens {
+    Tokens {
+        access_token: "access-1".to_string(),
+        refresh_token: Some("refresh-1".to_string()),
+        expires_at: chrono::Utc::now(),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn data_is_private_to_the_user() {
+    let parent = TempDir::new().unwrap();
+    let dir = parent.path().join("whoopterm");
+    let config = Config {
+        data_dir: Some(dir.clone()),
+        ..Default::default()
+    };
+
+    config.save_tokens(&tokens()).unwrap();
+    config.save_cache(&Default::default()).unwrap();
+
+    assert_eq!(mode(&dir), 0o700);
+    assert_eq!(mode(&dir.join("tokens.json")), 0o600);
+    assert_eq!(mode(&dir.join("cache.json")), 0o600);
+    let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(names.len(), 2, "no temp files left behind: {:?}", names);
+    assert!(config.permission_warnings().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn other_profiles_create_private_parent_directories() {
+    let parent = TempDir::new().unwrap();
+    let dir = parent.path().join("whoopterm");
+    let config = Config {
+        data_dir: Some(dir.clone()),
+        profile: Some("work".to_string()),
+        ..Default::default()
+    };
+
+    config.save_tokens(&tokens()).unwrap();
+
+    assert_eq!(mode(&dir), 0o700);
+    assert_eq!(mode(&dir.join("profiles")), 0o700);
+    assert_eq!(mode(&dir.join("profiles").join("work")), 0o700);
+    assert!(config.permission_warnings().is_empty());
+    assert!(Config { profile: None, ..config }.permission_warnings().is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn readable_tokens_are_reported_and_fixed_on_save() {
+    let dir = TempDir::new().unwrap();
+    let config = Config {
+        data_dir: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o700)).unwrap();
+    let path = dir.path().join("tokens.json");
+    fs::write(&path, "{}").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+    let warnings = config.permission_warnings();
+
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("tokens.json") && warnings[0].contains("chmod 600"), "{}", warnings[0]);
+    config.save_tokens(&tokens()).unwrap();
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(config.load_tokens().unwrap().access_token, "access-1");
+}
+
+#[test]
+fn later_layers_override_earlier_ones() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("config.json");
+    fs::write(&file, r#"{ "history_days": 14, "cache_ttl": 5, "client_id": "from-file" }"#).unwrap();
+    let env = |var: &str| match var {
+        "WHOOP_CACHE_TTL" => Some("90".to_string()),
+        "WHOOP_DATA_DIR" => Some("/tmp/whoop-data".to_string()),
+        _ => None,
+    };
+
+    let flags = Config { history_days: Some(3), ..Default::default() };
+    let config = Config::load_from(Some(&file), env, &flags).unwrap();
+
+    assert_eq!(config.cache_ttl(), chrono::Duration::minutes(90));
+    assert_eq!(config.data_dir.as_deref(), Some(std::path::Path::new("/tmp/whoop-data")));
+    assert_eq!(config.history_days, Some(3));
+    let origin = |key: &str| config.settings().into_iter().find(|(k, _, _)| k == key).unwrap().2;
+    assert_eq!(origin("client_id"), Origin::File(file.clone()));
+    assert_eq!(origin("cache_ttl"), Origin::Env("WHOOP_CACHE_TTL"));
+    assert_eq!(origin("history_days"), Origin::Cli);
+    assert_eq!(origin("token_url"), Origin::Default);
+}
+
+#[test]
+fn invalid_environment_values_are_rejected() {
+    for (var, value) in [("WHOOP_CACHE_TTL", "1h"), ("WHOOP_HISTORY_DAYS", "abc"), ("WHOOP_HISTORY_DAYS", "0"), ("WHOOP_RETENTION", "2yrs")] {
+        let env = |name: &str| (name == var).then(|| value.to_string());
+
+        let error = Config::load_from(None, env, &Config::default()).unwrap_err();
+
+        assert!(error.to_string().contains(var) && error.to_string().contains(value), "{:#}", error);
+    }
+}
+
+#[test]
+fn unknown_config_file_settings_are_rejected() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("config.json");
+    fs::write(&file, r#"{ "cache_tll": 5 }"#).unwrap();
+
+    let error = Config::load_from(Some(&file), |_| None, &Config::default()).unwrap_err();
+
+    assert!(format!("{:#}", error).contains("cache_tll"), "{:#}", error);
+}
+
+#[test]
+fn profiles_have_their_own_credentials_and_data() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("config.json");
+    let data = dir.path().join("data");
+    fs::write(&file, format!(r#"{{
+        "client_id": "shared",
+        "data_dir": {:?},
+        "profile": "alice",
+        "profiles": {{ "bob": {{ "client_id": "bobs-app" }} }}
+    }}"#, data)).unwrap();
+
+    let alice = Config::load_from(Some(&file), |_| None, &Config::default()).unwrap();
+    let bob = Config::load_from(Some(&file), |_| None, &Config { profile: Some("bob".to_string()), ..Default::default() }).unwrap();
+
+    assert_eq!(alice.profile(), "alice");
+    assert_eq!(alice.client_id.as_deref(), Some("shared"));
+    assert_eq!(alice.data_dir().unwrap(), data.join("profiles").join("alice"));
+    assert_eq!(bob.client_id.as_deref(), Some("bobs-app"));
+    assert_eq!(bob.data_dir().unwrap(), data.join("profiles").join("bob"));
+    bob.save_tokens(&tokens()).unwrap();
+    assert!(alice.load_tokens().is_err(), "tokens are per profile");
+    assert_eq!(alice.profile_names().unwrap(), ["alice", "bob", "default"]);
+    assert_eq!(Config { profile: None, ..alice }.data_dir().unwrap(), data);
+}
+
+#[test]
+fn environment_and_flags_override_profile_sections() {
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("config.json");
+    fs::write(&file, r#"{
+        "client_id": "shared",
+        "profiles": { "bob": { "client_id": "bobs-app", "client_secret": "bobs-secret" } }
+    }"#).unwrap();
+    let env = |var: &str| match var {
+        "WHOOP_PROFILE" => Some("bob".to_string()),
+        "WHOOP_CLIENT_ID" => Some("from-env".to_string()),
+        _ => None,
+    };
+
+    let config = Config::load_from(Some(&file), env, &Config::default()).unwrap();
+
+    assert_eq!(config.profile(), "bob");
+    assert_eq!(config.client_id.as_deref(), Some("from-env"));
+    assert_eq!(config.client_secret.as_deref(), Some("bobs-secret"));
+    let origin = |key: &str| config.settings().into_iter().find(|(k, _, _)| k == key).unwrap().2;
+    assert_eq!(origin("client_id"), Origin::Env("WHOOP_CLIENT_ID"));
+    assert_eq!(origin("client_secret"), Origin::Profile("bob".to_string(), file.clone()));
+
+    let flags = Config { client_id: Some("from-flag".to_string()), ..Default::default() };
+    let config = Config::load_from(Some(&file), env, &flags).unwrap();
+    assert_eq!(config.client_id.as_deref(), Some("from-flag"));
+}
+
+#[test]
+fn profile_names_must_be_safe_directory_names() {
+    let flags = Config { profile: Some("../other".to_string()), ..Default::default() };
+
+    assert!(Config::load_from(None, |_| None, &flags).is_err());
+}
+
+#[test]
+fn login_callback_listens_on_the_redirect_uri_port_unless_overridden() {
+    let redirect = |uri: &str| Config { redirect_uri: Some(uri.to_string()), ..Default::default() };
+
+    assert_eq!(Config::default().callback_bind().unwrap(), ("127.0.0.1".to_string(), 8080));
+    assert_eq!(redirect("http://localhost:8765/callback").callback_bind().unwrap().1, 8765);
+    let forwarded = Config { callback_address: Some("0.0.0.0".to_string()), callback_port: Some(9000), ..redirect("http://localhost:8765/callback") };
+    assert_eq!(forwarded.callback_bind().unwrap(), ("0.0.0.0".to_string(), 9000));
+}
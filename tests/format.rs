@@ -0,0 +1,76 @@
+mod common;
+
+use std::fs;
+
+use common::Stub;
+use serde_json::json;
+use tempfile::TempDir;
+use whoopterm::api::WhoopAPI;
+use whoopterm::config::Config;
+use whoopterm::data::{HistoryWindow, Section};
+use whoopterm::format::{is_too_new, FormatError, CACHE};
+
+fn config(dir: &TempDir) -> Config {
+    Config {
+        data_dir: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn unversioned_cache_is_migrated() {
+    let dir = TempDir::new().unwrap();
+    // A cache as written before the format was versioned
+    let legacy = json!({
+        "profile": null,
+        "recovery": [],
+        "sleep": [],
+        "workouts": [],
+        "refreshed_at": "2024-01-15T08:00:00Z",
+    });
+    fs::write(dir.path().join("cache.json"), legacy.to_string()).unwrap();
+
+    let cache = config(&dir).load_cache().unwrap();
+
+    assert_eq!(cache.window, HistoryWindow::Days(7));
+    assert!(cache.cycles.is_empty() && cache.journal.is_empty());
+    assert_eq!(cache.fetched_at.len(), Section::ALL.len());
+    assert!(cache.fetched_at.values().all(|at| Some(*at) == cache.refreshed_at));
+}
+
+#[test]
+fn saved_cache_records_its_version() {
+    let dir = TempDir::new().unwrap();
+    config(&dir).save_cache(&Default::default()).unwrap();
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join("cache.json")).unwrap()).unwrap();
+
+    assert_eq!(json["format_version"], CACHE.version());
+}
+
+#[tokio::test]
+async fn cache_from_a_newer_whoopterm_is_not_overwritten() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+    stub.mount_dashboard().await;
+    let newer = json!({ "format_version": CACHE.version() + 1, "something": "new" }).to_string();
+    let path = stub.dir.path().join("cache.json");
+    fs::write(&path, &newer).unwrap();
+
+    let error = WhoopAPI::with_config(stub.config()).refresh_all_data(HistoryWindow::default()).await.unwrap_err();
+
+    assert!(is_too_new(&error));
+    assert!(matches!(error.downcast_ref::<FormatError>(), Some(FormatError::TooNew { found, .. }) if *found == CACHE.version() + 1));
+    assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+}
+
+#[tokio::test]
+async fn tokens_from_a_newer_whoopterm_do_not_ask_for_login() {
+    let stub = Stub::start().await;
+    let newer = json!({ "format_version": 99, "access_token": "a", "expires_at": "2030-01-01T00:00:00Z" });
+    fs::write(stub.dir.path().join("tokens.json"), newer.to_string()).unwrap();
+
+    let error = whoopterm::auth::AuthManager::with_config(stub.config()).get_access_token().await.unwrap_err();
+
+    assert!(is_too_new(&error), "{:#}", error);
+}
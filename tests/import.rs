@@ -0,0 +1,162 @@
+use std::fs;
+use std::io::Write;
+
+use chrono::{DateTime, TimeZone, Utc};
+use tempfile::TempDir;
+use whoopterm::data::{Cycle, Recovery, Sleep, Workout};
+use whoopterm::import::{import, ImportCount};
+use whoopterm::store::Store;
+
+const CYCLES: &str = "\
+Cycle start time,Cycle end time,Cycle timezone,Recovery score %,Resting heart rate (bpm),Heart rate variability (ms),Skin temp (celsius),Blood oxygen %,Day Strain,Energy burned (cal),Max HR (bpm),Average HR (bpm),Sleep onset,Wake onset,Sleep performance %
+2024-03-01 23:10:00,2024-03-02 22:40:00,UTC-05:00,67,52,71,33.4,96.5,12.3,2390,171,68,2024-03-01 23:10:00,2024-03-02 07:05:00,88
+2024-03-02 22:40:00,,UTC-05:00,,,,,,4.1,900,120,61,2024-03-02 22:40:00,,
+";
+
+const SLEEPS: &str = "\
+Cycle start time,Cycle end time,Cycle timezone,Sleep onset,Wake onset,Sleep performance %,Respiratory rate (rpm),Asleep duration (min),In bed duration (min),Light sleep duration (min),Deep (SWS) duration (min),REM duration (min),Awake duration (min),Sleep need (min),Sleep debt (min),Sleep efficiency %,Sleep consistency %,Nap
+2024-03-01 23:10:00,2024-03-02 22:40:00,UTC-05:00,2024-03-01 23:10:00,2024-03-02 07:05:00,88,15.2,440,475,230,95,115,35,500,20,92,81,false
+";
+
+const WORKOUTS: &str = "\
+Cycle start time,Cycle end time,Cycle timezone,Workout start time,Workout end time,Duration (min),Activity name,Activity Strain,Energy burned (cal),Max HR (bpm),Average HR (bpm),HR Zone 1 %,HR Zone 2 %,HR Zone 3 %,HR Zone 4 %,HR Zone 5 %,GPS enabled
+2024-03-01 23:10:00,2024-03-02 22:40:00,UTC-05:00,2024-03-02 17:00:00,2024-03-02 18:00:00,60,Running,11.2,620,176,148,10,20,40,20,5,true
+";
+
+const JOURNAL: &str = "\
+Cycle start time,Cycle end time,Cycle timezone,Question text,Answered yes,Notes
+2024-03-01 23:10:00,2024-03-02 22:40:00,UTC-05:00,Have any alcoholic drinks?,true,Two glasses of wine
+2024-03-01 23:10:00,2024-03-02 22:40:00,UTC-05:00,Have any caffeine?,false,
+";
+
+fn write_export(dir: &TempDir) {
+    for (name, csv) in files() {
+        fs::write(dir.path().join(name), csv).unwrap();
+    }
+}
+
+fn files() -> [(&'static str, &'static str); 4] {
+    [
+        ("physiological_cycles.csv", CYCLES),
+        ("sleeps.csv", SLEEPS),
+        ("workouts.csv", WORKOUTS),
+        ("journal_entries.csv", JOURNAL),
+    ]
+}
+
+fn all_time() -> (DateTime<Utc>, DateTime<Utc>) {
+    (Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(), Utc::now())
+}
+
+fn count(imported: usize, duplicates: usize) -> ImportCount {
+    ImportCount { imported, duplicates }
+}
+
+#[test]
+fn export_folder_is_imported_into_the_store() {
+    let dir = TempDir::new().unwrap();
+    write_export(&dir);
+    let mut store = Store::in_memory().unwrap();
+
+    let report = import(dir.path(), &mut store, 10129).unwrap();
+
+    assert_eq!(report.counts, [
+        ("cycles", count(2, 0)),
+        ("sleep", count(1, 0)),
+        ("recovery", count(1, 0)),
+        ("workouts", count(1, 0)),
+        ("journal", count(2, 0)),
+    ]);
+
+    let (start, end) = all_time();
+    let sleeps: Vec<Sleep> = store.records(start, end).unwrap();
+    // Local times are converted using the cycle's timezone
+    assert_eq!(sleeps[0].start, Utc.with_ymd_and_hms(2024, 3, 2, 4, 10, 0).unwrap());
+    assert_eq!(sleeps[0].timezone_offset, "-05:00");
+    let stages = &sleeps[0].score.as_ref().unwrap().stage_summary;
+    assert_eq!(stages.total_in_bed_time_milli, 475 * 60_000);
+
+    let recoveries: Vec<Recovery> = store.records(start, end).unwrap();
+    let cycles: Vec<Cycle> = store.records(start, end).unwrap();
+    assert_eq!(recoveries[0].score.as_ref().unwrap().recovery_score, 67.0);
+    assert_eq!(recoveries[0].sleep_id, sleeps[0].id);
+    assert_eq!(recoveries[0].cycle_id, cycles[1].id);
+    assert!(cycles[0].end.is_none());
+
+    let workouts: Vec<Workout> = store.records(start, end).unwrap();
+    let score = workouts[0].score.as_ref().unwrap();
+    assert_eq!(workouts[0].sport_name, "running");
+    assert_eq!(score.zone_durations.zone_three_milli, 24 * 60_000);
+    assert_eq!(score.zone_durations.zone_zero_milli, 3 * 60_000);
+
+    let journal = store.journal(start, end).unwrap();
+    assert_eq!(journal.len(), 2);
+    assert_eq!(journal[0].question, "Have any alcoholic drinks?");
+    assert_eq!(journal[0].notes.as_deref(), Some("Two glasses of wine"));
+    assert_eq!(journal[1].notes, None);
+}
+
+#[test]
+fn reimporting_changes_nothing() {
+    let dir = TempDir::new().unwrap();
+    write_export(&dir);
+    let mut store = Store::in_memory().unwrap();
+
+    import(dir.path(), &mut store, 0).unwrap();
+    let report = import(dir.path(), &mut store, 0).unwrap();
+
+    assert!(report.counts.iter().all(|(_, c)| c.imported == 0), "{:?}", report.counts);
+    assert_eq!(report.counts[0].1, count(0, 2));
+}
+
+#[test]
+fn export_zip_is_read_from_a_nested_folder() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("my_whoop_data.zip");
+    let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+    for (name, csv) in files() {
+        zip.start_file(format!("my_whoop_data_2024_03_03/{}", name), zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(csv.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+    let mut store = Store::in_memory().unwrap();
+
+    let report = import(&path, &mut store, 0).unwrap();
+
+    assert_eq!(report.counts[3], ("workouts", count(1, 0)));
+}
+
+#[test]
+fn records_synced_from_the_api_win_over_the_export() {
+    let dir = TempDir::new().unwrap();
+    write_export(&dir);
+    let mut store = Store::in_memory().unwrap();
+    let (start, end) = all_time();
+
+    // Already synced: the API's copy of the workout, a few seconds off
+    let mut imported = Store::in_memory().unwrap();
+    import(dir.path(), &mut imported, 0).unwrap();
+    let mut synced: Workout = imported.records(start, end).unwrap().remove(0);
+    synced.id = "0f8c1f4a-api".to_string();
+    synced.start += chrono::Duration::seconds(2);
+    store.upsert(&[synced.clone()]).unwrap();
+
+    let report = import(dir.path(), &mut store, 0).unwrap();
+    assert_eq!(report.counts[3], ("workouts", count(0, 1)));
+
+    // Synced later: the API's copy replaces the imported one
+    let mut sleep: Sleep = store.records(start, end).unwrap().remove(0);
+    sleep.id = "93b1e5d2-api".to_string();
+    store.upsert(&[sleep]).unwrap();
+    let sleeps: Vec<Sleep> = store.records(start, end).unwrap();
+    let ids: Vec<_> = sleeps.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["93b1e5d2-api"]);
+}
+
+#[test]
+fn missing_export_files_are_reported() {
+    let dir = TempDir::new().unwrap();
+    let error = import(dir.path(), &mut Store::in_memory().unwrap(), 0).unwrap_err();
+    assert!(error.to_string().contains("No WHOOP export files"), "{}", error);
+}
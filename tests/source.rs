@@ -0,0 +1,178 @@
+mod common;
+
+use std::fs;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use common::{body_measurement, cycle, page, profile, recovery, sleep, workout};
+use serde_json::json;
+use tempfile::TempDir;
+use whoopterm::config::Config;
+use whoopterm::data::{BodyMeasurement, Cycle, DashboardData, HistoryWindow, JournalEntry, Profile, Recovery, Section, Sleep, Workout};
+use whoopterm::demo::DemoSource;
+use whoopterm::source::{CacheSource, DataSource, FixtureSource};
+use whoopterm::store::Store;
+
+/// Demo data with a journal that can't be read.
+struct BrokenJournal;
+
+#[async_trait]
+impl DataSource for BrokenJournal {
+    fn name(&self) -> &'static str {
+        "broken-journal"
+    }
+
+    async fn profile(&self) -> Result<Profile> {
+        DemoSource.profile().await
+    }
+
+    async fn body_measurement(&self) -> Result<BodyMeasurement> {
+        DemoSource.body_measurement().await
+    }
+
+    async fn recovery(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Recovery>> {
+        DemoSource.recovery(start, end).await
+    }
+
+    async fn sleep(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Sleep>> {
+        DemoSource.sleep(start, end).await
+    }
+
+    async fn workouts(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Workout>> {
+        DemoSource.workouts(start, end).await
+    }
+
+    async fn cycles(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<Vec<Cycle>> {
+        DemoSource.cycles(start, end).await
+    }
+
+    async fn journal(&self, _start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        anyhow::bail!("journal table is locked")
+    }
+}
+
+fn write_fixtures(dir: &TempDir) {
+    let files = [
+        ("profile.json", profile()),
+        ("body.json", body_measurement()),
+        ("recovery.json", page(vec![recovery(1)], None)),
+        ("sleep.json", json!([sleep("s1")])),
+        ("workouts.json", page(vec![workout("w1"), workout("w2")], None)),
+        ("cycles.json", page(vec![cycle(1)], None)),
+    ];
+    for (name, body) in files {
+        fs::write(dir.path().join(name), body.to_string()).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn fixture_source_replays_recorded_responses() {
+    let dir = TempDir::new().unwrap();
+    write_fixtures(&dir);
+
+    let data = FixtureSource::new(dir.path()).refresh(HistoryWindow::default()).await.unwrap();
+
+    assert!(data.errors.is_empty(), "unexpected errors: {:?}", data.errors);
+    assert_eq!(data.profile.unwrap().last_name, "Doe");
+    assert_eq!(data.sleep.len(), 1);
+    assert_eq!(data.workouts.len(), 2);
+}
+
+#[tokio::test]
+async fn fixtures_older_than_the_window_are_still_replayed() {
+    let dir = TempDir::new().unwrap();
+    write_fixtures(&dir);
+    let mut old = workout("w-old");
+    for field in ["created_at", "updated_at", "start", "end"] {
+        old[field] = json!("2023-03-01T10:00:00Z");
+    }
+    fs::write(dir.path().join("workouts.json"), json!([old]).to_string()).unwrap();
+
+    let data = FixtureSource::new(dir.path()).refresh(HistoryWindow::Days(7)).await.unwrap();
+
+    assert_eq!(data.workouts.len(), 1);
+    assert_eq!(data.workouts[0].id, "w-old");
+}
+
+#[tokio::test]
+async fn missing_fixture_fails_only_its_section() {
+    let dir = TempDir::new().unwrap();
+    write_fixtures(&dir);
+    fs::remove_file(dir.path().join("cycles.json")).unwrap();
+
+    let data = FixtureSource::new(dir.path()).refresh(HistoryWindow::default()).await.unwrap();
+
+    assert_eq!(data.errors.len(), 1);
+    assert!(data.cycles.is_empty());
+    assert_eq!(data.recovery.len(), 1);
+}
+
+#[tokio::test]
+async fn failing_journal_is_reported_like_other_sections() {
+    let data = BrokenJournal.refresh(HistoryWindow::Days(7)).await.unwrap();
+
+    assert_eq!(data.errors.keys().collect::<Vec<_>>(), [&Section::Journal]);
+    assert!(data.errors[&Section::Journal].contains("locked"));
+    assert!(!data.sleep.is_empty() && !data.cycles.is_empty());
+    assert!(data.fetched_any());
+    assert!(data.refreshed_at.is_some());
+}
+
+#[tokio::test]
+async fn demo_source_is_stable_and_within_window() {
+    let window = HistoryWindow::Days(14);
+    let (start, end) = window.bounds();
+
+    let first = DemoSource.refresh(window).await.unwrap();
+    let second = DemoSource.refresh(window).await.unwrap();
+
+    assert!(!first.sleep.is_empty() && first.sleep.len() <= 14, "got {} nights", first.sleep.len());
+    assert!(first.sleep.iter().all(|s| s.end >= start && s.end < end));
+    assert!(first.sleep.windows(2).all(|w| w[0].start > w[1].start), "newest first");
+    assert_eq!(first.cycles.iter().filter(|c| c.end.is_none()).count(), 1);
+    assert!(first.cycles.windows(2).all(|w| w[1].end == Some(w[0].start)), "cycles run wake to wake");
+    let ids = |d: &DashboardData| d.workouts.iter().map(|w| w.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&first), ids(&second));
+}
+
+#[tokio::test]
+async fn cache_source_serves_stored_records_in_window() {
+    let dir = TempDir::new().unwrap();
+    let config = Config {
+        data_dir: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    let mut cached = DemoSource.refresh(HistoryWindow::Days(30)).await.unwrap();
+    cached.refreshed_at = Some(Utc::now() - Duration::days(2));
+    config.save_cache(&cached).unwrap();
+    let mut store = Store::open(&config).unwrap();
+    store.upsert(&cached.recovery).unwrap();
+    store.upsert(&cached.sleep).unwrap();
+    store.upsert(&cached.workouts).unwrap();
+    store.upsert(&cached.cycles).unwrap();
+
+    let data = CacheSource::new(config).refresh(HistoryWindow::Days(7)).await.unwrap();
+
+    let cutoff = Utc::now() - Duration::days(7);
+    assert!(data.errors.is_empty(), "unexpected errors: {:?}", data.errors);
+    assert!(!data.sleep.is_empty());
+    assert!(data.sleep.iter().all(|s| s.start >= cutoff));
+    assert!(data.sleep.len() < cached.sleep.len());
+}
+
+#[tokio::test]
+async fn days_pair_journal_answers_with_their_cycle() {
+    let data = DemoSource.refresh(HistoryWindow::Days(14)).await.unwrap();
+
+    let days = data.days();
+
+    assert!(!data.journal.is_empty());
+    assert_eq!(days.len(), data.cycles.len(), "every answer belongs to a demo cycle");
+    assert!(days.windows(2).all(|w| w[0].date >= w[1].date), "newest first");
+    assert_eq!(days.iter().map(|d| d.journal.len()).sum::<usize>(), data.journal.len());
+    let answered = days.iter().find(|d| !d.journal.is_empty()).unwrap();
+    let cycle = answered.cycle.as_ref().unwrap();
+    assert!(answered.journal.iter().all(|e| e.cycle_start == cycle.start));
+    assert_eq!(answered.recovery.as_ref().map(|r| r.cycle_id), Some(cycle.id));
+}
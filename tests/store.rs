@@ -0,0 +1,134 @@
+mod common;
+
+use common::{sleep, workout, Stub};
+use serde_json::json;
+use whoopterm::api::WhoopAPI;
+use whoopterm::data::{HistoryWindow, Recovery, Section, Sleep, Workout};
+use whoopterm::store::{Retention, Store};
+
+fn parse<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn upsert_keeps_the_latest_version() {
+    let mut store = Store::in_memory().unwrap();
+    let mut original: Workout = parse(workout("w1"));
+    original.sport_name = "running".to_string();
+    let mut rescored = original.clone();
+    rescored.sport_name = "cycling".to_string();
+    rescored.updated_at = original.updated_at + chrono::Duration::hours(1);
+
+    assert_eq!(store.upsert(&[original.clone()]).unwrap(), 1);
+    assert_eq!(store.upsert(&[rescored]).unwrap(), 1);
+    // A stale copy, e.g. from an older export, doesn't overwrite the newer one
+    assert_eq!(store.upsert(&[original]).unwrap(), 0);
+
+    let (start, end) = HistoryWindow::Days(7).bounds();
+    let stored: Vec<Workout> = store.records(start, end).unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].sport_name, "cycling");
+}
+
+#[tokio::test]
+async fn refresh_keeps_history_outside_the_window() {
+    let stub = Stub::start().await;
+    stub.save_tokens("access-1", chrono::Duration::hours(1));
+    let mut old = sleep("s-old");
+    old["start"] = json!(chrono::Utc::now() - chrono::Duration::days(40));
+    stub.mount_json("/v2/activity/sleep", common::page(vec![sleep("s1"), old], None)).await;
+    stub.mount_dashboard().await;
+
+    let data = WhoopAPI::with_config(stub.config()).refresh_all_data(HistoryWindow::default()).await.unwrap();
+    assert_eq!(data.sleep.len(), 1);
+
+    let store = Store::open(&stub.config()).unwrap();
+    let (start, end) = HistoryWindow::Days(60).bounds();
+    let stored: Vec<Sleep> = store.records(start, end).unwrap();
+    let ids: Vec<_> = stored.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["s1", "s-old"]);
+}
+
+#[test]
+fn store_from_a_newer_whoopterm_is_rejected() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let config = whoopterm::config::Config {
+        data_dir: Some(dir.path().to_path_buf()),
+        ..Default::default()
+    };
+    drop(Store::open(&config).unwrap());
+    let conn = rusqlite::Connection::open(dir.path().join("history.db")).unwrap();
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+    assert!(version > 0, "new stores are versioned");
+    conn.pragma_update(None, "user_version", version + 1).unwrap();
+    drop(conn);
+
+    let error = Store::open(&config).err().unwrap();
+
+    assert!(whoopterm::format::is_too_new(&error), "{:#}", error);
+}
+
+#[test]
+fn prune_drops_old_history_and_resyncs_it() {
+    let mut store = Store::in_memory().unwrap();
+    let now = chrono::Utc::now();
+    let mut old: Sleep = parse(sleep("s-old"));
+    old.start = now - chrono::Duration::days(400);
+    store.upsert(&[old, parse::<Sleep>(sleep("s1"))]).unwrap();
+    let mut sync = whoopterm::sync::SyncState::default();
+    sync.record::<Sleep>(now - chrono::Duration::days(500), now, &[]);
+    store.save_sync_state(&sync).unwrap();
+    let keep: Retention = "1y".parse().unwrap();
+
+    let deleted = store.prune(keep.cutoff(now)).unwrap();
+
+    assert!(deleted.contains(&("sleep", 1)), "{:?}", deleted);
+    let stats = store.stats().unwrap();
+    assert_eq!(stats.iter().find(|t| t.table == "sleep").unwrap().records, 1);
+    let floor = now - chrono::Duration::days(450);
+    assert_eq!(store.sync_state().unwrap().fetch_from(Section::Sleep, floor), floor, "pruned range is fetched again");
+}
+
+#[test]
+fn pruning_during_a_night_takes_its_recovery_along() {
+    let mut store = Store::in_memory().unwrap();
+    let night: Sleep = parse(sleep("s1"));
+    let mut recovery: Recovery = parse(common::recovery(1));
+    recovery.sleep_id = night.id.clone();
+    assert!(recovery.created_at > night.start);
+    let cutoff = night.start + chrono::Duration::hours(1);
+    store.upsert(&[night]).unwrap();
+    store.upsert(&[recovery]).unwrap();
+
+    let deleted = store.prune(cutoff).unwrap();
+
+    assert!(deleted.contains(&("sleep", 1)) && deleted.contains(&("recovery", 1)), "{:?}", deleted);
+    assert_eq!(store.verify().unwrap(), []);
+}
+
+#[test]
+fn verify_reports_recoveries_without_their_sleep() {
+    let mut store = Store::in_memory().unwrap();
+    let mut recovery: Recovery = parse(common::recovery(1));
+    recovery.sleep_id = "missing".to_string();
+    let mut matched = recovery.clone();
+    matched.cycle_id = 2;
+    matched.sleep_id = "s1".to_string();
+    store.upsert(&[recovery, matched]).unwrap();
+    store.upsert(&[parse::<Sleep>(sleep("s1"))]).unwrap();
+
+    let issues = store.verify().unwrap();
+
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!((issues[0].table, issues[0].id.as_str()), ("recovery", "1"));
+    assert!(issues[0].problem.contains("missing"));
+}
+
+#[test]
+fn retention_ages_parse() {
+    assert_eq!("90d".parse(), Ok(Retention::Days(90)));
+    assert_eq!("2y".parse(), Ok(Retention::Years(2)));
+    assert!("2".parse::<Retention>().is_err());
+    assert!("0m".parse::<Retention>().is_err());
+    assert!("y".parse::<Retention>().is_err());
+}